@@ -0,0 +1,604 @@
+/*
+Compute unit benchmarks of update_quotes.
+
+Every market layout in SUPPORTED_MARKET_SIZES is built from scratch in LiteSVM (header,
+empty trees, seats) and its book is seeded with `depth` orders per side from other traders.
+For each (size, depth) three paths are measured:
+
+  fresh      no resting quotes, both orders are placed
+  identical  our quotes already rest at the computed prices, nothing is sent to Phoenix
+  cancel     our quotes rest one level away, they are cancelled and replaced
+
+Results are compared with cu_baseline.json and the run fails when a case grows by more than
+`tolerance_bps` or has no baseline entry. `--update` rewrites the baseline instead.
+
+  cargo run -p bench --release -- [--update] [--phoenix <phoenix.so>] [--phoenix-mm <phoenix_mm.so>]
+
+Program binaries default to the paths used by the client config (`phoenix.so` in the
+workspace root, `target/deploy/phoenix_mm.so`).
+*/
+use anyhow::{Context, Result, anyhow, bail};
+use bytemuck::Zeroable;
+use litesvm::LiteSVM;
+use litesvm::types::TransactionMetadata;
+use phoenix_mm::sdk::*;
+use phoenix_mm::types::*;
+use phoenix_mm::utils::*;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::Account, keccak, program_pack::Pack, pubkey::Pubkey, signature::Keypair,
+    signer::Signer, transaction::Transaction,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Phoenix `CancelMultipleOrdersByIdWithFreeFunds` and `PlaceMultiplePostOnlyOrders`
+const PHOENIX_CANCEL_MULTIPLE_DISCRIMINANT: u8 = 11;
+const PHOENIX_PLACE_MULTIPLE_POST_ONLY_DISCRIMINANT: u8 = 16;
+
+// SOL/USDC-like market: 0.001 SOL lots, 1 micro USDC quote lots, 0.001 USDC ticks
+const BASE_DECIMALS: u32 = 9;
+const QUOTE_DECIMALS: u32 = 6;
+const BASE_LOT_SIZE: u64 = 1_000_000;
+const QUOTE_LOT_SIZE: u64 = 1;
+const TICK_SIZE_IN_QUOTE_ATOMS_PER_BASE_UNIT: u64 = 1_000;
+const FAIR_PRICE_IN_QUOTE_ATOMS_PER_RAW_BASE_UNIT: u64 = 150_000_000;
+const QUOTE_EDGE_IN_BPS: u64 = 10;
+const QUOTE_SIZE_IN_QUOTE_ATOMS: u64 = 100_000_000;
+// seeded orders use sequence numbers far above the ones Phoenix hands out during the run
+const SEEDED_SEQUENCE_NUMBER_START: u64 = 1 << 40;
+const BOOK_DEPTHS: [u64; 4] = [0, 16, 128, 500];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Path_ {
+    Fresh,
+    Identical,
+    Cancel,
+}
+impl Path_ {
+    const ALL: [Path_; 3] = [Path_::Fresh, Path_::Identical, Path_::Cancel];
+    fn name(&self) -> &'static str {
+        match self {
+            Path_::Fresh => "fresh",
+            Path_::Identical => "identical",
+            Path_::Cancel => "cancel",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Baseline {
+    /// Allowed growth before a case counts as a regression
+    tolerance_bps: u64,
+    /// "<bids>x<asks>x<seats> depth=<n> <path>" -> compute units
+    results: BTreeMap<String, u64>,
+}
+
+struct Args {
+    update: bool,
+    phoenix: PathBuf,
+    phoenix_mm: PathBuf,
+}
+impl Args {
+    fn parse() -> Result<Self> {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+        let mut args = Args {
+            update: false,
+            phoenix: root.join("phoenix.so"),
+            phoenix_mm: root.join("target/deploy/phoenix_mm.so"),
+        };
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--update" => args.update = true,
+                "--phoenix" => args.phoenix = iter.next().context("--phoenix needs a path")?.into(),
+                "--phoenix-mm" => {
+                    args.phoenix_mm = iter.next().context("--phoenix-mm needs a path")?.into()
+                }
+                _ => bail!("unknown argument {}", arg),
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse()?;
+    let baseline_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("cu_baseline.json");
+    let mut baseline: Baseline = serde_json::from_str(
+        &std::fs::read_to_string(&baseline_path)
+            .with_context(|| format!("reading {:?}", baseline_path))?,
+    )?;
+    let phoenix = read_program(&args.phoenix)?;
+    let phoenix_mm = read_program(&args.phoenix_mm)?;
+
+    let mut results = BTreeMap::new();
+    for (bids_size, asks_size, num_seats) in SUPPORTED_MARKET_SIZES {
+        let size_params = MarketSizeParams {
+            bids_size,
+            asks_size,
+            num_seats,
+        };
+        for depth in BOOK_DEPTHS.iter().map(|d| (*d).min(bids_size - 4)) {
+            for path in Path_::ALL {
+                let name = format!(
+                    "{}x{}x{} depth={} {}",
+                    bids_size,
+                    asks_size,
+                    num_seats,
+                    depth,
+                    path.name()
+                );
+                let units = run_case(&phoenix, &phoenix_mm, &size_params, depth, path)
+                    .with_context(|| name.clone())?;
+                results.insert(name, units);
+            }
+        }
+    }
+
+    if args.update {
+        baseline.results = results;
+        std::fs::write(
+            &baseline_path,
+            serde_json::to_string_pretty(&baseline)? + "\n",
+        )?;
+        println!("wrote {:?}", baseline_path);
+        return Ok(());
+    }
+
+    let mut regressions = 0;
+    let mut missing = 0;
+    println!(
+        "{:<40} {:>10} {:>10} {:>8}",
+        "case", "baseline", "current", "delta"
+    );
+    for (name, units) in results.iter() {
+        // a case without a baseline is not checked at all, so it fails the run too
+        let Some(&expected) = baseline.results.get(name) else {
+            println!("{:<40} {:>10} {:>10} {:>8}", name, "-", units, "MISSING");
+            missing += 1;
+            continue;
+        };
+        let delta_bps = (*units as i128 - expected as i128) * 10_000 / expected.max(1) as i128;
+        let regressed = delta_bps > baseline.tolerance_bps as i128;
+        regressions += regressed as usize;
+        println!(
+            "{:<40} {:>10} {:>10} {:>7.2}%{}",
+            name,
+            expected,
+            units,
+            delta_bps as f64 / 100.0,
+            if regressed { "  REGRESSION" } else { "" }
+        );
+    }
+    if missing > 0 {
+        bail!(
+            "{} case(s) have no baseline, run with --update and commit cu_baseline.json",
+            missing
+        );
+    }
+    if regressions > 0 {
+        bail!(
+            "{} case(s) grew by more than {} bps, run with --update if this is expected",
+            regressions,
+            baseline.tolerance_bps
+        );
+    }
+    Ok(())
+}
+
+fn read_program(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("reading program {:?}", path))
+}
+
+fn run_case(
+    phoenix: &[u8],
+    phoenix_mm: &[u8],
+    size_params: &MarketSizeParams,
+    depth: u64,
+    path: Path_,
+) -> Result<u64> {
+    let mut litesvm = LiteSVM::new();
+    litesvm
+        .add_program(PHOENIX_PROGRAM_ID, phoenix)
+        .map_err(|e| anyhow!("loading phoenix: {:?}", e))?;
+    litesvm
+        .add_program(PROGRAM_ID, phoenix_mm)
+        .map_err(|e| anyhow!("loading phoenix_mm: {:?}", e))?;
+
+    let trader = Keypair::new();
+    let market = Pubkey::new_unique();
+    let base_mint = Pubkey::new_unique();
+    let quote_mint = Pubkey::new_unique();
+    litesvm
+        .airdrop(&trader.pubkey(), 10_000_000_000)
+        .map_err(|e| anyhow!("{:?}", e.err))?;
+    set_account(
+        &mut litesvm,
+        base_mint,
+        mint_account(BASE_DECIMALS),
+        spl_token::id(),
+    )?;
+    set_account(
+        &mut litesvm,
+        quote_mint,
+        mint_account(QUOTE_DECIMALS),
+        spl_token::id(),
+    )?;
+
+    let header = market_header(market, base_mint, quote_mint, size_params);
+    let accounts = UpdateQuotesAccounts::new(&market, &header, &trader.pubkey());
+    for (vault, mint) in [
+        (accounts.base_vault, base_mint),
+        (accounts.quote_vault, quote_mint),
+    ] {
+        let owner = Pubkey::find_program_address(
+            &[b"vault", market.as_ref(), mint.as_ref()],
+            &PHOENIX_PROGRAM_ID,
+        )
+        .0;
+        set_account(
+            &mut litesvm,
+            vault,
+            token_account(mint, owner, 0),
+            spl_token::id(),
+        )?;
+    }
+    set_account(
+        &mut litesvm,
+        accounts.base_account,
+        token_account(base_mint, trader.pubkey(), 1_000 * 10u64.pow(BASE_DECIMALS)),
+        spl_token::id(),
+    )?;
+    set_account(
+        &mut litesvm,
+        accounts.quote_account,
+        token_account(
+            quote_mint,
+            trader.pubkey(),
+            1_000_000 * 10u64.pow(QUOTE_DECIMALS),
+        ),
+        spl_token::id(),
+    )?;
+    set_account(
+        &mut litesvm,
+        accounts.seat,
+        seat_account(&market, &trader.pubkey()),
+        PHOENIX_PROGRAM_ID,
+    )?;
+
+    // the quotes update_quotes computes for this fair price (Ignore behavior)
+    let bid_price = get_bid_price_in_ticks(
+        FAIR_PRICE_IN_QUOTE_ATOMS_PER_RAW_BASE_UNIT,
+        &header,
+        QUOTE_EDGE_IN_BPS,
+    );
+    let ask_price = get_ask_price_in_ticks(
+        FAIR_PRICE_IN_QUOTE_ATOMS_PER_RAW_BASE_UNIT,
+        &header,
+        QUOTE_EDGE_IN_BPS,
+    );
+    let base_lots_per_base_unit = 10u64.pow(BASE_DECIMALS) / BASE_LOT_SIZE;
+    let tick_size = TICK_SIZE_IN_QUOTE_ATOMS_PER_BASE_UNIT / QUOTE_LOT_SIZE;
+    let size_in_quote_lots = QUOTE_SIZE_IN_QUOTE_ATOMS / QUOTE_LOT_SIZE;
+    let bid_size = size_in_quote_lots * base_lots_per_base_unit / (bid_price * tick_size);
+    let ask_size = size_in_quote_lots * base_lots_per_base_unit / (ask_price * tick_size);
+
+    let mut data = vec![0u8; get_market_size(size_params).map_err(|e| anyhow!("{:?}", e))?];
+    data[..size_of::<MarketHeader>()].copy_from_slice(bytemuck::bytes_of(&header));
+    let mut strategy = PhoenixStrategyState::zeroed();
+    strategy.trader = trader.pubkey().to_bytes();
+    strategy.market = market.to_bytes();
+    strategy.version = STRATEGY_STATE_VERSION;
+    {
+        let book =
+            deserialize_market_mut(&mut data, size_params).map_err(|e| anyhow!("{:?}", e))?;
+        book.initialize_with_params(tick_size, base_lots_per_base_unit);
+        let trader_index = book
+            .get_or_register_trader(&trader.pubkey().to_bytes())
+            .context("registering trader")?;
+        // half of the seats are taken so trader lookups walk a realistic tree
+        let fillers = (1..size_params.num_seats / 2)
+            .map(|_| {
+                let filler = Pubkey::new_unique().to_bytes();
+                book.get_or_register_trader(&filler)
+                    .map(|index| (filler, index))
+            })
+            .collect::<Option<Vec<_>>>()
+            .context("registering fillers")?;
+        let mut sequence_number = SEEDED_SEQUENCE_NUMBER_START;
+        for level in 0..depth {
+            let (filler, filler_index) = fillers[level as usize % fillers.len()];
+            // other traders rest strictly behind our quotes, one order per level
+            let orders = [
+                (Side::Bid, bid_price - 10 - level, bid_size),
+                (Side::Ask, ask_price + 10 + level, ask_size),
+            ];
+            for (side, price, size) in orders {
+                insert_order(
+                    book,
+                    &filler,
+                    filler_index,
+                    side,
+                    price,
+                    size,
+                    sequence_number,
+                    base_lots_per_base_unit,
+                    tick_size,
+                )?;
+                sequence_number += 1;
+            }
+        }
+        let resting_prices = match path {
+            Path_::Fresh => None,
+            Path_::Identical => Some((bid_price, ask_price)),
+            Path_::Cancel => Some((bid_price - 1, ask_price + 1)),
+        };
+        if let Some((resting_bid, resting_ask)) = resting_prices {
+            let bid_id = insert_order(
+                book,
+                &trader.pubkey().to_bytes(),
+                trader_index,
+                Side::Bid,
+                resting_bid,
+                bid_size,
+                sequence_number,
+                base_lots_per_base_unit,
+                tick_size,
+            )?;
+            let ask_id = insert_order(
+                book,
+                &trader.pubkey().to_bytes(),
+                trader_index,
+                Side::Ask,
+                resting_ask,
+                ask_size,
+                sequence_number + 1,
+                base_lots_per_base_unit,
+                tick_size,
+            )?;
+            strategy.bid_price_in_ticks = resting_bid;
+            strategy.bid_order_sequence_number = bid_id.order_sequence_number;
+            strategy.initial_bid_size_in_base_lots = bid_size;
+            strategy.ask_price_in_ticks = resting_ask;
+            strategy.ask_order_sequence_number = ask_id.order_sequence_number;
+            strategy.initial_ask_size_in_base_lots = ask_size;
+        }
+    }
+    litesvm.set_account(
+        market,
+        Account {
+            lamports: litesvm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: PHOENIX_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    )?;
+    litesvm.set_account(
+        accounts.strategy,
+        Account {
+            lamports: litesvm.minimum_balance_for_rent_exemption(size_of::<PhoenixStrategyState>()),
+            data: bytemuck::bytes_of(&strategy).to_vec(),
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    )?;
+
+    let params = StrategyParams {
+        quote_edge_in_bps: QUOTE_EDGE_IN_BPS,
+        quote_size_in_quote_atoms: QUOTE_SIZE_IN_QUOTE_ATOMS,
+        price_improvement_behavior: PriceImprovementBehavior::Ignore.to_u8(),
+        post_only: 1,
+        cover_taker_fee: 0,
+        hedge_cost_in_bps: 0,
+        requote_tolerance_in_ticks: 0,
+        keep_remaining_in_pct: 0,
+        min_best_size_in_pct: 0,
+        min_best_size_in_base_lots: 0,
+        best_price_depth_in_base_lots: 0,
+        pricing_model: 0,
+        risk_aversion_in_millionths: 0,
+        order_arrival_decay_in_millionths: 0,
+        volatility_in_bps: 0,
+        volatility_span_in_slots: 0,
+        volatility_edge_multiplier_in_hundredths: 0,
+    };
+    let ix = update_quotes_ix(
+        &accounts,
+        FAIR_PRICE_IN_QUOTE_ATOMS_PER_RAW_BASE_UNIT,
+        &params,
+    );
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&trader.pubkey()),
+        &[&trader],
+        litesvm.latest_blockhash(),
+    );
+    let account_keys = tx.message.account_keys.clone();
+    let meta = litesvm.send_transaction(tx).map_err(|failed| {
+        anyhow!(
+            "update_quotes failed: {:?}\n{}",
+            failed.err,
+            failed.meta.pretty_logs()
+        )
+    })?;
+    check_path(&meta, &account_keys, path)?;
+    Ok(meta.compute_units_consumed)
+}
+
+// make sure the case exercised the path it is named after
+fn check_path(meta: &TransactionMetadata, account_keys: &[Pubkey], path: Path_) -> Result<()> {
+    let phoenix_discriminants = meta
+        .inner_instructions
+        .iter()
+        .flatten()
+        .filter(|inner| {
+            account_keys.get(inner.instruction.program_id_index as usize)
+                == Some(&PHOENIX_PROGRAM_ID)
+        })
+        .filter_map(|inner| inner.instruction.data.first().copied())
+        .collect::<Vec<_>>();
+    let cancelled = phoenix_discriminants.contains(&PHOENIX_CANCEL_MULTIPLE_DISCRIMINANT);
+    let placed = phoenix_discriminants.contains(&PHOENIX_PLACE_MULTIPLE_POST_ONLY_DISCRIMINANT);
+    let expected = match path {
+        Path_::Fresh => !cancelled && placed,
+        Path_::Identical => phoenix_discriminants.is_empty(),
+        Path_::Cancel => cancelled && placed,
+    };
+    if !expected {
+        bail!(
+            "{} case took another path, phoenix instructions: {:?}",
+            path.name(),
+            phoenix_discriminants
+        );
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_order(
+    book: &mut dyn WritableMarket<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    trader: &[u8; 32],
+    trader_index: u32,
+    side: Side,
+    price_in_ticks: u64,
+    num_base_lots: u64,
+    sequence_number: u64,
+    base_lots_per_base_unit: u64,
+    tick_size: u64,
+) -> Result<FIFOOrderId> {
+    // bid sequence numbers are stored inverted, see Side::from_order_sequence_number
+    let order_id = FIFOOrderId::new_from_untyped(
+        price_in_ticks,
+        match side {
+            Side::Bid => !sequence_number,
+            Side::Ask => sequence_number,
+        },
+    );
+    book.get_book_mut(side)
+        .insert(
+            order_id,
+            FIFORestingOrder {
+                trader_index: trader_index as u64,
+                num_base_lots,
+                last_valid_slot: 0,
+                last_valid_unix_timestamp_in_seconds: 0,
+            },
+        )
+        .context("book is full")?;
+    // lock the funds backing the order, as Phoenix would have
+    let state = book
+        .get_registered_traders_mut()
+        .get_mut(trader)
+        .context("trader is not registered")?;
+    match side {
+        Side::Bid => {
+            state.quote_lots_locked.inner +=
+                price_in_ticks * tick_size * num_base_lots / base_lots_per_base_unit
+        }
+        Side::Ask => state.base_lots_locked.inner += num_base_lots,
+    }
+    Ok(order_id)
+}
+
+fn market_header(
+    market: Pubkey,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    size_params: &MarketSizeParams,
+) -> MarketHeader {
+    let mut header = MarketHeader::zeroed();
+    header.discriminant = phoenix_discriminant("phoenix::program::accounts::MarketHeader");
+    // MarketStatus::Active
+    header.status = 1;
+    header.market_size_params = *size_params;
+    for (params, mint, decimals) in [
+        (&mut header.base_params, base_mint, BASE_DECIMALS),
+        (&mut header.quote_params, quote_mint, QUOTE_DECIMALS),
+    ] {
+        let (vault, bump) = Pubkey::find_program_address(
+            &[b"vault", market.as_ref(), mint.as_ref()],
+            &PHOENIX_PROGRAM_ID,
+        );
+        params.decimals = decimals;
+        params.vault_bump = bump as u32;
+        params.mint_key = mint.to_bytes();
+        params.vault_key = vault.to_bytes();
+    }
+    header.base_lot_size = BASE_LOT_SIZE;
+    header.quote_lot_size = QUOTE_LOT_SIZE;
+    header.tick_size_in_quote_atoms_per_base_unit = TICK_SIZE_IN_QUOTE_ATOMS_PER_BASE_UNIT;
+    header.raw_base_units_per_base_unit = 1;
+    header
+}
+
+// Phoenix account discriminants: keccak(program id, type name)[..8]
+fn phoenix_discriminant(type_name: &str) -> u64 {
+    u64::from_le_bytes(
+        keccak::hashv(&[PHOENIX_PROGRAM_ID.as_ref(), type_name.as_bytes()]).as_ref()[..8]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+fn seat_account(market: &Pubkey, trader: &Pubkey) -> Vec<u8> {
+    let mut data = Vec::with_capacity(128);
+    data.extend_from_slice(&phoenix_discriminant("phoenix::program::accounts::Seat").to_le_bytes());
+    data.extend_from_slice(market.as_ref());
+    data.extend_from_slice(trader.as_ref());
+    // approval_status: Approved
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.extend_from_slice(&[0u8; 48]);
+    data
+}
+
+fn mint_account(decimals: u32) -> Vec<u8> {
+    let mut data = vec![0u8; spl_token::state::Mint::LEN];
+    spl_token::state::Mint::pack(
+        spl_token::state::Mint {
+            mint_authority: None.into(),
+            supply: u64::MAX / 2,
+            decimals: decimals as u8,
+            is_initialized: true,
+            freeze_authority: None.into(),
+        },
+        &mut data,
+    )
+    .unwrap();
+    data
+}
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account::pack(
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            delegate: None.into(),
+            state: spl_token::state::AccountState::Initialized,
+            is_native: None.into(),
+            delegated_amount: 0,
+            close_authority: None.into(),
+        },
+        &mut data,
+    )
+    .unwrap();
+    data
+}
+
+fn set_account(litesvm: &mut LiteSVM, address: Pubkey, data: Vec<u8>, owner: Pubkey) -> Result<()> {
+    litesvm.set_account(
+        address,
+        Account {
+            lamports: litesvm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        },
+    )?;
+    Ok(())
+}
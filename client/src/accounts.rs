@@ -0,0 +1,57 @@
+use anyhow::{Result, anyhow};
+use phoenix_mm::sdk::*;
+use phoenix_mm::types::*;
+use phoenix_mm::utils::*;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+/// Every account the bot needs for one Phoenix market, derived from the market address,
+/// its header and the trader
+#[derive(Clone, Copy)]
+pub struct MarketAccounts {
+    pub header: MarketHeader,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub seat_manager: Pubkey,
+    pub seat_deposit_collector: Pubkey,
+    /// Accounts of the update_quotes instruction
+    pub update_quotes: UpdateQuotesAccounts,
+}
+impl MarketAccounts {
+    pub fn from_header(market: Pubkey, header: &MarketHeader, trader: Pubkey) -> Self {
+        MarketAccounts {
+            header: *header,
+            base_mint: Pubkey::new_from_array(header.base_params.mint_key),
+            quote_mint: Pubkey::new_from_array(header.quote_params.mint_key),
+            seat_manager: get_seat_manager_address(&market).0,
+            seat_deposit_collector: get_seat_deposit_collector_address(&market).0,
+            update_quotes: UpdateQuotesAccounts::new(&market, header, &trader),
+        }
+    }
+
+    /// Read the market header over RPC and derive everything from it
+    pub fn fetch(rpc: &RpcClient, market: Pubkey, trader: Pubkey) -> Result<Self> {
+        let data = rpc.get_account_data(&market)?;
+        let header = deserialize_market_header(
+            data.get(..size_of::<MarketHeader>())
+                .ok_or_else(|| anyhow!("market {} is too small", market))?,
+        )
+        .map_err(|e| anyhow!("market {}: {:?}", market, e))?;
+        Ok(Self::from_header(market, &header, trader))
+    }
+
+    /// Accounts mirrored from mainnet before each update
+    pub fn hydrated_accounts(&self) -> Vec<Pubkey> {
+        vec![
+            self.update_quotes.trader,
+            PHOENIX_LOG_AUTHORITY,
+            self.update_quotes.strategy,
+            self.seat_manager,
+            self.seat_deposit_collector,
+            self.base_mint,
+            self.quote_mint,
+            self.update_quotes.base_vault,
+            self.update_quotes.quote_vault,
+        ]
+    }
+}
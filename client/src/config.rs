@@ -0,0 +1,412 @@
+use crate::fees::ComputeBudgetConfig;
+use crate::ledger::ledger_path;
+use crate::logging::LogFormat;
+use crate::quoter::QuoterConfig;
+use anyhow::{Context, Result, anyhow, bail};
+use phoenix_mm::pricing::{MAX_RISK_AVERSION_IN_MILLIONTHS, MAX_VOLATILITY_IN_BPS};
+use phoenix_mm::types::{PriceImprovementBehavior, PricingModel};
+use serde::{Deserialize, Deserializer};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::EncodableKey};
+use std::{collections::HashSet, env, net::SocketAddr, path::PathBuf, time::Duration};
+
+/// Config file used when `PHOENIX_MM_CONFIG` is not set
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Client configuration, see `config.example.toml`
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    pub rpc: RpcConfig,
+    /// Keypair used by markets that do not set their own
+    pub keypair_path: Option<PathBuf>,
+    #[serde(default)]
+    pub programs: ProgramsConfig,
+    #[serde(default)]
+    pub quoter: QuoterSettings,
+    /// Directory of the per-market fill ledgers
+    #[serde(default = "default_ledger_dir")]
+    pub ledger_dir: PathBuf,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub compute_budget: ComputeBudgetConfig,
+    /// Address of the Prometheus `/metrics` endpoint, disabled when unset
+    pub metrics_addr: Option<SocketAddr>,
+    pub markets: Vec<MarketConfig>,
+}
+
+fn default_ledger_dir() -> PathBuf {
+    "ledger".into()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RpcConfig {
+    pub url: String,
+    pub origin_header: Option<String>,
+}
+
+/// Program binaries loaded into LiteSVM
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ProgramsConfig {
+    pub phoenix_mm: PathBuf,
+    pub phoenix: PathBuf,
+    pub seat_manager: PathBuf,
+}
+impl Default for ProgramsConfig {
+    fn default() -> Self {
+        ProgramsConfig {
+            phoenix_mm: "../target/deploy/phoenix_mm.so".into(),
+            phoenix: "../phoenix.so".into(),
+            seat_manager: "../phoniex_seat_manager.so".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QuoterSettings {
+    pub update_interval_ms: u64,
+    pub price_threshold_bps: u64,
+    pub max_quote_age_slots: u64,
+    pub max_backoff_ms: u64,
+}
+impl Default for QuoterSettings {
+    fn default() -> Self {
+        QuoterSettings {
+            update_interval_ms: 2_000,
+            price_threshold_bps: 1,
+            max_quote_age_slots: 150,
+            max_backoff_ms: 60_000,
+        }
+    }
+}
+impl From<&QuoterSettings> for QuoterConfig {
+    fn from(settings: &QuoterSettings) -> Self {
+        QuoterConfig {
+            update_interval: Duration::from_millis(settings.update_interval_ms),
+            price_threshold_in_bps: settings.price_threshold_bps,
+            max_quote_age_in_slots: settings.max_quote_age_slots,
+            max_backoff: Duration::from_millis(settings.max_backoff_ms),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MarketConfig {
+    pub name: String,
+    /// Phoenix market address, mints and vaults are read from its header
+    #[serde(deserialize_with = "pubkey_from_str")]
+    pub address: Pubkey,
+    pub keypair_path: Option<PathBuf>,
+    /// Simulated wallet balances (UI units) of the dummy token accounts
+    pub base_balance: f64,
+    pub quote_balance: f64,
+    pub strategy: StrategyConfig,
+    pub price: PriceConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StrategyConfig {
+    pub quote_edge_in_bps: u64,
+    /// Order notional size in UI quote units (e.g. 500 USDC)
+    pub quote_size: f64,
+    /// One of `join`, `dime` or `ignore`
+    pub price_improvement_behavior: String,
+    #[serde(default)]
+    pub post_only: bool,
+    /// Never quote closer to fair price than the market taker fee + `hedge_cost_in_bps`
+    #[serde(default)]
+    pub cover_taker_fee: bool,
+    #[serde(default)]
+    pub hedge_cost_in_bps: u16,
+    /// Keep a resting quote within this many ticks of the new price...
+    #[serde(default)]
+    pub requote_tolerance_in_ticks: u8,
+    /// ...if at least this share (%) of it is left, 0 always requotes
+    #[serde(default)]
+    pub keep_remaining_in_pct: u8,
+    /// Ignore levels smaller than this share (%) of our quote size when joining/diming
+    #[serde(default)]
+    pub min_best_size_in_pct: u8,
+    /// Ignore levels smaller than this when joining/diming
+    #[serde(default)]
+    pub min_best_size_in_base_lots: u64,
+    /// Join/dime the size-weighted price of this many base lots, 0 uses the top level
+    #[serde(default)]
+    pub best_price_depth_in_base_lots: u64,
+    /// One of `fixed_edge` (quote_edge_in_bps around the fair price) or `avellaneda_stoikov`
+    #[serde(default = "default_pricing_model")]
+    pub pricing_model: String,
+    /// Avellaneda-Stoikov gamma, per bps
+    #[serde(default)]
+    pub risk_aversion: f64,
+    /// Avellaneda-Stoikov kappa, per bps
+    #[serde(default)]
+    pub order_arrival_decay: f64,
+    /// Volatility of the fair price over the quoting horizon
+    #[serde(default)]
+    pub volatility_in_bps: u64,
+    /// Span of the on-chain EWMA variance of fair price returns, 0 disables it
+    #[serde(default)]
+    pub volatility_span_in_slots: u64,
+    /// Quote at least this many per-slot EWMA sigmas away from the fair price
+    #[serde(default)]
+    pub volatility_edge_multiplier: f64,
+}
+fn default_pricing_model() -> String {
+    "fixed_edge".to_string()
+}
+impl StrategyConfig {
+    pub fn price_improvement_behavior(&self) -> Result<PriceImprovementBehavior> {
+        match self.price_improvement_behavior.as_str() {
+            "join" => Ok(PriceImprovementBehavior::Join),
+            "dime" => Ok(PriceImprovementBehavior::Dime),
+            "ignore" => Ok(PriceImprovementBehavior::Ignore),
+            other => bail!("unknown price_improvement_behavior: {}", other),
+        }
+    }
+
+    pub fn pricing_model(&self) -> Result<PricingModel> {
+        match self.pricing_model.as_str() {
+            "fixed_edge" => Ok(PricingModel::FixedEdge),
+            "avellaneda_stoikov" => Ok(PricingModel::AvellanedaStoikov),
+            other => bail!("unknown pricing_model: {}", other),
+        }
+    }
+
+    pub fn risk_aversion_in_millionths(&self) -> u64 {
+        (self.risk_aversion * 1e6).round() as u64
+    }
+
+    pub fn order_arrival_decay_in_millionths(&self) -> u64 {
+        (self.order_arrival_decay * 1e6).round() as u64
+    }
+
+    pub fn volatility_edge_multiplier_in_hundredths(&self) -> u16 {
+        (self.volatility_edge_multiplier * 100.0).round() as u16
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PriceConfig {
+    pub sources: Vec<PriceSourceConfig>,
+    #[serde(default = "default_max_age_secs")]
+    pub max_age_secs: u64,
+    #[serde(default = "default_max_deviation_bps")]
+    pub max_deviation_bps: u64,
+    #[serde(default = "default_min_sources")]
+    pub min_sources: usize,
+}
+fn default_max_age_secs() -> u64 {
+    30
+}
+fn default_max_deviation_bps() -> u64 {
+    50
+}
+fn default_min_sources() -> usize {
+    1
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PriceSourceConfig {
+    Coinbase {
+        product: String,
+    },
+    Binance {
+        symbol: String,
+    },
+    Pyth {
+        #[serde(deserialize_with = "pubkey_from_str")]
+        account: Pubkey,
+    },
+    File {
+        path: PathBuf,
+    },
+    Static {
+        price: f64,
+    },
+}
+
+fn pubkey_from_str<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+impl Config {
+    /// Read the config file, apply env var overrides and validate it
+    pub fn load() -> Result<Self> {
+        let path = env::var("PHOENIX_MM_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.into());
+        let contents =
+            std::fs::read_to_string(&path).with_context(|| format!("reading config {}", path))?;
+        let mut config: Config =
+            toml::from_str(&contents).with_context(|| format!("parsing config {}", path))?;
+        config.apply_env_overrides()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env_overrides(&mut self) -> Result<()> {
+        if let Ok(url) = env::var("RPC_URL") {
+            self.rpc.url = url;
+        }
+        if let Ok(origin) = env::var("ORIGIN_HEADER") {
+            self.rpc.origin_header = Some(origin);
+        }
+        if let Ok(path) = env::var("KEYPAIR_PATH") {
+            self.keypair_path = Some(path.into());
+        }
+        if let Ok(dir) = env::var("LEDGER_DIR") {
+            self.ledger_dir = dir.into();
+        }
+        if let Ok(format) = env::var("LOG_FORMAT") {
+            self.log_format = format.parse()?;
+        }
+        if let Ok(addr) = env::var("METRICS_ADDR") {
+            self.metrics_addr = Some(addr.parse().context("invalid METRICS_ADDR")?);
+        }
+        let parse = |key: &str| -> Result<Option<u64>> {
+            env::var(key)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {}", key)))
+                .transpose()
+        };
+        if let Some(v) = parse("UPDATE_INTERVAL_MS")? {
+            self.quoter.update_interval_ms = v;
+        }
+        if let Some(v) = parse("PRICE_THRESHOLD_BPS")? {
+            self.quoter.price_threshold_bps = v;
+        }
+        if let Some(v) = parse("MAX_QUOTE_AGE_SLOTS")? {
+            self.quoter.max_quote_age_slots = v;
+        }
+        if let Some(v) = parse("MAX_BACKOFF_MS")? {
+            self.quoter.max_backoff_ms = v;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.rpc.url.is_empty() {
+            bail!("rpc.url is empty");
+        }
+        if self.markets.is_empty() {
+            bail!("no markets configured");
+        }
+        if self.quoter.update_interval_ms == 0 {
+            bail!("quoter.update_interval_ms must be positive");
+        }
+        if self.quoter.max_backoff_ms < self.quoter.update_interval_ms {
+            bail!("quoter.max_backoff_ms must be at least quoter.update_interval_ms");
+        }
+        self.compute_budget.validate()?;
+        // the strategy PDA is derived from the trader only, so one keypair can run one market
+        let mut traders = HashSet::new();
+        let mut ledgers = HashSet::new();
+        for market in self.markets.iter() {
+            let ctx = |msg: &str| anyhow!("market {}: {}", market.name, msg);
+            if !ledgers.insert(ledger_path(&self.ledger_dir, &market.name)) {
+                return Err(ctx("name maps to the same ledger file as another market"));
+            }
+            let keypair_path = self.keypair_path(market)?;
+            let trader = Keypair::read_from_file(keypair_path)
+                .map_err(|e| ctx(&format!("reading keypair {:?}: {}", keypair_path, e)))?;
+            if !traders.insert(solana_sdk::signer::Signer::pubkey(&trader)) {
+                return Err(ctx("keypair is already used by another market"));
+            }
+            market.strategy.price_improvement_behavior()?;
+            if market.strategy.quote_edge_in_bps >= 10_000 {
+                return Err(ctx("strategy.quote_edge_in_bps must be below 10000"));
+            }
+            if market.strategy.hedge_cost_in_bps >= 10_000 {
+                return Err(ctx("strategy.hedge_cost_in_bps must be below 10000"));
+            }
+            if market.strategy.keep_remaining_in_pct > 100 {
+                return Err(ctx("strategy.keep_remaining_in_pct must be at most 100"));
+            }
+            // the program bounds these for every model, not only the one that uses them
+            if !(market.strategy.risk_aversion >= 0.0)
+                || market.strategy.risk_aversion_in_millionths() > MAX_RISK_AVERSION_IN_MILLIONTHS
+            {
+                return Err(ctx("strategy.risk_aversion must be between 0 and 1000"));
+            }
+            if market.strategy.volatility_in_bps > MAX_VOLATILITY_IN_BPS {
+                return Err(ctx("strategy.volatility_in_bps must be at most 10000"));
+            }
+            if let PricingModel::AvellanedaStoikov = market.strategy.pricing_model()? {
+                // checked in millionths, the unit the program rejects 0 in
+                if market.strategy.order_arrival_decay_in_millionths() == 0 {
+                    return Err(ctx(
+                        "strategy.order_arrival_decay must be at least 0.000001",
+                    ));
+                }
+            }
+            if !(0.0..=u16::MAX as f64 / 100.0)
+                .contains(&market.strategy.volatility_edge_multiplier)
+            {
+                return Err(ctx(
+                    "strategy.volatility_edge_multiplier must be between 0 and 655.35",
+                ));
+            }
+            if !(market.strategy.quote_size > 0.0) {
+                return Err(ctx("strategy.quote_size must be positive"));
+            }
+            if market.base_balance < 0.0 || market.quote_balance < 0.0 {
+                return Err(ctx("balances must not be negative"));
+            }
+            if market.price.sources.is_empty() {
+                return Err(ctx("price.sources is empty"));
+            }
+            if market.price.min_sources > market.price.sources.len() {
+                return Err(ctx(
+                    "price.min_sources is larger than the number of sources",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keypair of a market, falling back to the top level one
+    pub fn keypair_path<'a>(&'a self, market: &'a MarketConfig) -> Result<&'a PathBuf> {
+        market
+            .keypair_path
+            .as_ref()
+            .or(self.keypair_path.as_ref())
+            .ok_or_else(|| anyhow!("market {}: no keypair_path configured", market.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example config, with a keypair that exists
+    fn example() -> Config {
+        let mut config: Config = toml::from_str(include_str!("../config.example.toml")).unwrap();
+        let path = env::temp_dir().join(format!("config-test-{}.json", std::process::id()));
+        if !path.exists() {
+            solana_sdk::signer::EncodableKey::write_to_file(&Keypair::new(), &path).unwrap();
+        }
+        config.keypair_path = Some(path);
+        config
+    }
+
+    #[test]
+    fn model_parameters_are_bounded_for_every_model() {
+        let config = example();
+        assert!(matches!(
+            config.markets[0].strategy.pricing_model().unwrap(),
+            PricingModel::FixedEdge
+        ));
+        config.validate().unwrap();
+
+        let mut config = example();
+        config.markets[0].strategy.risk_aversion = 1_000.5;
+        assert!(config.validate().is_err());
+
+        let mut config = example();
+        config.markets[0].strategy.volatility_in_bps = MAX_VOLATILITY_IN_BPS + 1;
+        assert!(config.validate().is_err());
+        std::fs::remove_file(config.keypair_path.unwrap()).unwrap();
+    }
+}
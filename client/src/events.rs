@@ -0,0 +1,413 @@
+/*
+Decoder for the events Phoenix emits through its log authority.
+
+Every Phoenix instruction that touches the book flushes its events as a self-CPI into the
+`Log` instruction (discriminant 15). The instruction data is
+
+  [15] ++ borsh(PhoenixMarketEvent::Header(AuditLogHeader)) ++ borsh(PhoenixMarketEvent)*
+
+so a batch is decoded by reading the 92 byte header and then events until the data ends.
+Layouts mirror phoenix-v1 `program/events.rs`; keys are kept as raw [u8; 32].
+*/
+use anyhow::{Result, anyhow, bail};
+use borsh::{BorshDeserialize, BorshSerialize};
+use litesvm::types::TransactionMetadata;
+use phoenix_mm::sdk::PHOENIX_PROGRAM_ID;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status_client_types::{
+    EncodedConfirmedTransactionWithStatusMeta, UiInstruction, option_serializer::OptionSerializer,
+};
+use std::str::FromStr;
+
+// Phoenix `Log`
+pub const PHOENIX_LOG_DISCRIMINANT: u8 = 15;
+// tag + AuditLogHeader
+pub const AUDIT_LOG_HEADER_LEN: usize = 92;
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditLogHeader {
+    pub instruction: u8,
+    pub sequence_number: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+    pub market: [u8; 32],
+    pub signer: [u8; 32],
+    pub total_events: u16,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillEvent {
+    pub index: u16,
+    pub maker_id: [u8; 32],
+    pub order_sequence_number: u64,
+    pub price_in_ticks: u64,
+    pub base_lots_filled: u64,
+    pub base_lots_remaining: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaceEvent {
+    pub index: u16,
+    pub order_sequence_number: u64,
+    pub client_order_id: u128,
+    pub price_in_ticks: u64,
+    pub base_lots_placed: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReduceEvent {
+    pub index: u16,
+    pub order_sequence_number: u64,
+    pub price_in_ticks: u64,
+    pub base_lots_removed: u64,
+    pub base_lots_remaining: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvictEvent {
+    pub index: u16,
+    pub maker_id: [u8; 32],
+    pub order_sequence_number: u64,
+    pub price_in_ticks: u64,
+    pub base_lots_evicted: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillSummaryEvent {
+    pub index: u16,
+    pub client_order_id: u128,
+    pub total_base_lots_filled: u64,
+    pub total_quote_lots_filled: u64,
+    pub total_fee_in_quote_lots: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEvent {
+    pub index: u16,
+    pub fees_collected_in_quote_lots: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeInForceEvent {
+    pub index: u16,
+    pub order_sequence_number: u64,
+    pub last_valid_slot: u64,
+    pub last_valid_unix_timestamp_in_seconds: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpiredOrderEvent {
+    pub index: u16,
+    pub maker_id: [u8; 32],
+    pub order_sequence_number: u64,
+    pub price_in_ticks: u64,
+    pub base_lots_removed: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhoenixMarketEvent {
+    Uninitialized,
+    Header(AuditLogHeader),
+    Fill(FillEvent),
+    Place(PlaceEvent),
+    Reduce(ReduceEvent),
+    Evict(EvictEvent),
+    FillSummary(FillSummaryEvent),
+    Fee(FeeEvent),
+    TimeInForce(TimeInForceEvent),
+    ExpiredOrder(ExpiredOrderEvent),
+}
+
+/// Events of one `Log` instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhoenixEventBatch {
+    pub header: AuditLogHeader,
+    pub events: Vec<PhoenixMarketEvent>,
+}
+impl PhoenixEventBatch {
+    /// Events that concern `trader`: everything in batches it signed, plus fills, evictions
+    /// and expiries of its resting orders caused by other signers
+    pub fn trader_events<'a>(
+        &'a self,
+        trader: &'a Pubkey,
+    ) -> impl Iterator<Item = &'a PhoenixMarketEvent> + 'a {
+        let signed = self.header.signer == trader.to_bytes();
+        self.events.iter().filter(move |event| {
+            signed
+                || match event {
+                    PhoenixMarketEvent::Fill(e) => e.maker_id == trader.to_bytes(),
+                    PhoenixMarketEvent::Evict(e) => e.maker_id == trader.to_bytes(),
+                    PhoenixMarketEvent::ExpiredOrder(e) => e.maker_id == trader.to_bytes(),
+                    _ => false,
+                }
+        })
+    }
+}
+
+/// Decode the data of a Phoenix `Log` instruction, discriminant included
+pub fn decode_log_instruction(data: &[u8]) -> Result<PhoenixEventBatch> {
+    let (&discriminant, mut data) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("empty log instruction"))?;
+    if discriminant != PHOENIX_LOG_DISCRIMINANT {
+        bail!("not a phoenix log instruction: {}", discriminant);
+    }
+    if data.len() < AUDIT_LOG_HEADER_LEN {
+        bail!("log instruction shorter than the audit log header");
+    }
+    let header = match PhoenixMarketEvent::deserialize(&mut data)? {
+        PhoenixMarketEvent::Header(header) => header,
+        event => bail!("log batch starts with {:?} instead of a header", event),
+    };
+    let mut events = Vec::with_capacity(header.total_events as usize);
+    while !data.is_empty() {
+        events.push(PhoenixMarketEvent::deserialize(&mut data)?);
+    }
+    Ok(PhoenixEventBatch { header, events })
+}
+
+fn is_phoenix_log(program_id: Option<&Pubkey>, data: &[u8]) -> bool {
+    program_id == Some(&PHOENIX_PROGRAM_ID) && data.first() == Some(&PHOENIX_LOG_DISCRIMINANT)
+}
+
+/// Every event batch of a transaction executed in LiteSVM. `account_keys` are the static
+/// keys of the transaction message
+pub fn decode_litesvm_events(
+    meta: &TransactionMetadata,
+    account_keys: &[Pubkey],
+) -> Result<Vec<PhoenixEventBatch>> {
+    meta.inner_instructions
+        .iter()
+        .flatten()
+        .map(|inner| &inner.instruction)
+        .filter(|ix| is_phoenix_log(account_keys.get(ix.program_id_index as usize), &ix.data))
+        .map(|ix| decode_log_instruction(&ix.data))
+        .collect()
+}
+
+/// Every event batch of a transaction fetched over RPC, which must be binary encoded
+/// (base64) so the message account keys can be resolved
+pub fn decode_rpc_events(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<PhoenixEventBatch>> {
+    let meta = tx
+        .transaction
+        .meta
+        .as_ref()
+        .ok_or_else(|| anyhow!("transaction has no status meta"))?;
+    let transaction = tx
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| anyhow!("transaction is not binary encoded"))?;
+    let mut account_keys = transaction.message.static_account_keys().to_vec();
+    // lookup table keys follow the static keys, writable first
+    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            account_keys.push(Pubkey::from_str(key)?);
+        }
+    }
+    let OptionSerializer::Some(inner_instructions) = &meta.inner_instructions else {
+        return Ok(vec![]);
+    };
+    let mut batches = vec![];
+    for ix in inner_instructions
+        .iter()
+        .flat_map(|inner| &inner.instructions)
+    {
+        let UiInstruction::Compiled(ix) = ix else {
+            bail!("parsed inner instructions are not supported");
+        };
+        let data = bs58::decode(&ix.data).into_vec()?;
+        if is_phoenix_log(account_keys.get(ix.program_id_index as usize), &data) {
+            batches.push(decode_log_instruction(&data)?);
+        }
+    }
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{instruction::Instruction, transaction::Transaction};
+    use solana_transaction_status_client_types::{
+        EncodedTransaction, EncodedTransactionWithStatusMeta, UiCompiledInstruction,
+        UiInnerInstructions, UiTransactionStatusMeta,
+    };
+
+    const MAKER: [u8; 32] = [3; 32];
+
+    fn header(total_events: u16) -> AuditLogHeader {
+        AuditLogHeader {
+            instruction: 2,
+            sequence_number: 77,
+            timestamp: 1_760_000_000,
+            slot: 350_000_000,
+            market: [1; 32],
+            signer: [2; 32],
+            total_events,
+        }
+    }
+
+    fn events() -> Vec<PhoenixMarketEvent> {
+        vec![
+            PhoenixMarketEvent::Fill(FillEvent {
+                index: 0,
+                maker_id: MAKER,
+                order_sequence_number: !5,
+                price_in_ticks: 1_000,
+                base_lots_filled: 4,
+                base_lots_remaining: 6,
+            }),
+            PhoenixMarketEvent::Place(PlaceEvent {
+                index: 1,
+                order_sequence_number: 9,
+                client_order_id: u128::MAX - 1,
+                price_in_ticks: 1_010,
+                base_lots_placed: 20,
+            }),
+            PhoenixMarketEvent::Reduce(ReduceEvent {
+                index: 2,
+                order_sequence_number: 8,
+                price_in_ticks: 1_020,
+                base_lots_removed: 3,
+                base_lots_remaining: 0,
+            }),
+            PhoenixMarketEvent::Evict(EvictEvent {
+                index: 3,
+                maker_id: MAKER,
+                order_sequence_number: 7,
+                price_in_ticks: 1_030,
+                base_lots_evicted: 11,
+            }),
+            PhoenixMarketEvent::FillSummary(FillSummaryEvent {
+                index: 4,
+                client_order_id: 42,
+                total_base_lots_filled: 4,
+                total_quote_lots_filled: 4_000,
+                total_fee_in_quote_lots: 2,
+            }),
+        ]
+    }
+
+    fn log_data(header: AuditLogHeader, events: &[PhoenixMarketEvent]) -> Vec<u8> {
+        let mut data = vec![PHOENIX_LOG_DISCRIMINANT];
+        data.extend(PhoenixMarketEvent::Header(header).try_to_vec().unwrap());
+        for event in events {
+            data.extend(event.try_to_vec().unwrap());
+        }
+        data
+    }
+
+    #[test]
+    fn log_batch_round_trip() {
+        let events = events();
+        let data = log_data(header(events.len() as u16), &events);
+        assert_eq!(
+            PhoenixMarketEvent::Header(header(5))
+                .try_to_vec()
+                .unwrap()
+                .len(),
+            AUDIT_LOG_HEADER_LEN
+        );
+        let batch = decode_log_instruction(&data).unwrap();
+        assert_eq!(batch.header, header(5));
+        assert_eq!(batch.events, events);
+        // only the fill and the eviction are ours when someone else signed
+        assert_eq!(
+            batch.trader_events(&Pubkey::new_from_array(MAKER)).count(),
+            2
+        );
+        assert_eq!(
+            batch
+                .trader_events(&Pubkey::new_from_array([2; 32]))
+                .count(),
+            5
+        );
+    }
+
+    #[test]
+    fn malformed_log_data_is_an_error() {
+        let data = log_data(header(5), &events());
+        assert!(decode_log_instruction(&[]).is_err());
+        // another Phoenix instruction
+        let mut other = data.clone();
+        other[0] = 2;
+        assert!(decode_log_instruction(&other).is_err());
+        // truncated header
+        assert!(decode_log_instruction(&data[..AUDIT_LOG_HEADER_LEN]).is_err());
+        // truncated last event
+        assert!(decode_log_instruction(&data[..data.len() - 1]).is_err());
+        // no header first
+        let mut headless = vec![PHOENIX_LOG_DISCRIMINANT];
+        headless.extend(vec![0u8; AUDIT_LOG_HEADER_LEN]);
+        assert!(decode_log_instruction(&headless).is_err());
+    }
+
+    // a transaction calling Phoenix, whose inner instructions are `inner` (all run by Phoenix)
+    fn rpc_transaction(inner: &[Vec<u8>]) -> EncodedConfirmedTransactionWithStatusMeta {
+        let payer = Pubkey::new_unique();
+        let transaction = Transaction::new_with_payer(
+            &[Instruction::new_with_bytes(PHOENIX_PROGRAM_ID, &[], vec![])],
+            Some(&payer),
+        );
+        let phoenix_index = transaction
+            .message
+            .account_keys
+            .iter()
+            .position(|key| *key == PHOENIX_PROGRAM_ID)
+            .unwrap() as u8;
+        let instructions = inner
+            .iter()
+            .map(|data| {
+                UiInstruction::Compiled(UiCompiledInstruction {
+                    program_id_index: phoenix_index,
+                    accounts: vec![],
+                    data: bs58::encode(data).into_string(),
+                    stack_height: Some(2),
+                })
+            })
+            .collect();
+        let mut meta: UiTransactionStatusMeta = serde_json::from_value(serde_json::json!({
+            "err": null,
+            "status": { "Ok": null },
+            "fee": 5_000,
+            "preBalances": [],
+            "postBalances": [],
+        }))
+        .unwrap();
+        meta.inner_instructions = OptionSerializer::Some(vec![UiInnerInstructions {
+            index: 0,
+            instructions,
+        }]);
+        EncodedConfirmedTransactionWithStatusMeta {
+            slot: 350_000_000,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::LegacyBinary(
+                    bs58::encode(bincode::serialize(&transaction).unwrap()).into_string(),
+                ),
+                meta: Some(meta),
+                version: None,
+            },
+            block_time: None,
+        }
+    }
+
+    #[test]
+    fn rpc_events_skip_other_instructions() {
+        let events = events();
+        let log = log_data(header(events.len() as u16), &events);
+        // a Phoenix instruction that is not a log, around the log
+        let batches =
+            decode_rpc_events(&rpc_transaction(&[vec![2, 1, 2, 3], log.clone(), vec![]])).unwrap();
+        assert_eq!(
+            batches,
+            vec![PhoenixEventBatch {
+                header: header(5),
+                events
+            }]
+        );
+        // a truncated log fails the transaction instead of dropping its events
+        assert!(decode_rpc_events(&rpc_transaction(&[log[..40].to_vec()])).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+/*
+Compute unit limit and priority fee of our transactions.
+
+Every transaction is simulated first; the limit is the simulated consumption plus headroom,
+clamped to [min_cu_limit, max_cu_limit]. The priority fee (micro lamports per CU) comes from
+a PriorityFeeStrategy applied to the recent prioritization fees of the market account.
+The selection functions are pure so they can be checked without a cluster.
+*/
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+/// Hard cap of a transaction
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ComputeBudgetConfig {
+    /// Extra CU on top of the simulated consumption, in bps (2000 = +20%)
+    pub cu_headroom_bps: u64,
+    pub min_cu_limit: u32,
+    pub max_cu_limit: u32,
+    pub priority_fee: PriorityFeeStrategy,
+}
+impl Default for ComputeBudgetConfig {
+    fn default() -> Self {
+        ComputeBudgetConfig {
+            cu_headroom_bps: 2_000,
+            min_cu_limit: 10_000,
+            max_cu_limit: MAX_COMPUTE_UNIT_LIMIT,
+            priority_fee: PriorityFeeStrategy::default(),
+        }
+    }
+}
+impl ComputeBudgetConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_cu_limit > self.max_cu_limit {
+            bail!("compute_budget.min_cu_limit must not exceed compute_budget.max_cu_limit");
+        }
+        if self.max_cu_limit > MAX_COMPUTE_UNIT_LIMIT {
+            bail!(
+                "compute_budget.max_cu_limit must be at most {}",
+                MAX_COMPUTE_UNIT_LIMIT
+            );
+        }
+        self.priority_fee.validate()
+    }
+
+    /// CU limit for a transaction that consumed `simulated_units` in simulation
+    pub fn compute_unit_limit(&self, simulated_units: u64) -> u32 {
+        let limit = simulated_units as u128 * (10_000 + self.cu_headroom_bps as u128) / 10_000;
+        limit.clamp(self.min_cu_limit as u128, self.max_cu_limit as u128) as u32
+    }
+}
+
+/// How the priority fee is chosen, in micro lamports per CU
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PriorityFeeStrategy {
+    Fixed {
+        micro_lamports_per_cu: u64,
+    },
+    /// Percentile (0-100) of the recent fees
+    Percentile {
+        percentile: u8,
+    },
+    /// Percentile of the recent fees scaled by `multiplier_bps`, clamped to [min, max]
+    CappedDynamic {
+        percentile: u8,
+        multiplier_bps: u64,
+        min_micro_lamports_per_cu: u64,
+        max_micro_lamports_per_cu: u64,
+    },
+}
+impl Default for PriorityFeeStrategy {
+    fn default() -> Self {
+        PriorityFeeStrategy::Fixed {
+            micro_lamports_per_cu: 0,
+        }
+    }
+}
+impl PriorityFeeStrategy {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PriorityFeeStrategy::Fixed { .. } => {}
+            PriorityFeeStrategy::Percentile { percentile } => check_percentile(*percentile)?,
+            PriorityFeeStrategy::CappedDynamic {
+                percentile,
+                min_micro_lamports_per_cu,
+                max_micro_lamports_per_cu,
+                ..
+            } => {
+                check_percentile(*percentile)?;
+                if min_micro_lamports_per_cu > max_micro_lamports_per_cu {
+                    bail!("priority_fee.min_micro_lamports_per_cu must not exceed the max");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `select` needs the recent fees
+    pub fn is_dynamic(&self) -> bool {
+        !matches!(self, PriorityFeeStrategy::Fixed { .. })
+    }
+
+    /// Priority fee for the given recent fees
+    pub fn select(&self, recent_fees: &[u64]) -> u64 {
+        match self {
+            PriorityFeeStrategy::Fixed {
+                micro_lamports_per_cu,
+            } => *micro_lamports_per_cu,
+            PriorityFeeStrategy::Percentile { percentile } => {
+                fee_percentile(recent_fees, *percentile)
+            }
+            PriorityFeeStrategy::CappedDynamic {
+                percentile,
+                multiplier_bps,
+                min_micro_lamports_per_cu,
+                max_micro_lamports_per_cu,
+            } => {
+                let fee = fee_percentile(recent_fees, *percentile) as u128
+                    * *multiplier_bps as u128
+                    / 10_000;
+                fee.clamp(
+                    *min_micro_lamports_per_cu as u128,
+                    *max_micro_lamports_per_cu as u128,
+                ) as u64
+            }
+        }
+    }
+
+    /// Priority fee for the next transaction touching `accounts`
+    pub fn fetch(&self, rpc: &RpcClient, accounts: &[Pubkey]) -> Result<u64> {
+        if !self.is_dynamic() {
+            return Ok(self.select(&[]));
+        }
+        let recent_fees = rpc
+            .get_recent_prioritization_fees(accounts)?
+            .iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect::<Vec<_>>();
+        Ok(self.select(&recent_fees))
+    }
+}
+
+fn check_percentile(percentile: u8) -> Result<()> {
+    if percentile > 100 {
+        bail!("priority_fee.percentile must be at most 100");
+    }
+    Ok(())
+}
+
+/// Nearest-rank percentile, 0 when there are no fees
+pub fn fee_percentile(fees: &[u64], percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    let mut sorted = fees.to_vec();
+    sorted.sort_unstable();
+    let rank = (percentile.min(100) as usize * sorted.len()).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEES: [u64; 10] = [70, 10, 100, 40, 20, 90, 30, 60, 50, 80];
+
+    fn capped(percentile: u8, multiplier_bps: u64, min: u64, max: u64) -> PriorityFeeStrategy {
+        PriorityFeeStrategy::CappedDynamic {
+            percentile,
+            multiplier_bps,
+            min_micro_lamports_per_cu: min,
+            max_micro_lamports_per_cu: max,
+        }
+    }
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        for (percentile, fee) in [
+            (0, 10),
+            (1, 10),
+            (10, 10),
+            (11, 20),
+            (50, 50),
+            (95, 100),
+            (100, 100),
+        ] {
+            assert_eq!(
+                PriorityFeeStrategy::Percentile { percentile }.select(&FEES),
+                fee,
+                "percentile {}",
+                percentile
+            );
+        }
+        assert_eq!(
+            PriorityFeeStrategy::Percentile { percentile: 50 }.select(&[7]),
+            7
+        );
+    }
+
+    #[test]
+    fn no_recent_fees() {
+        assert_eq!(
+            PriorityFeeStrategy::Percentile { percentile: 75 }.select(&[]),
+            0
+        );
+        // the floor still applies
+        assert_eq!(capped(75, 20_000, 1_000, 5_000).select(&[]), 1_000);
+        assert_eq!(
+            PriorityFeeStrategy::Fixed {
+                micro_lamports_per_cu: 42
+            }
+            .select(&[]),
+            42
+        );
+    }
+
+    #[test]
+    fn capped_dynamic_scales_then_clamps() {
+        // 50th percentile is 50, times 1.5
+        assert_eq!(capped(50, 15_000, 0, 1_000).select(&FEES), 75);
+        assert_eq!(capped(50, 15_000, 0, 60).select(&FEES), 60);
+        assert_eq!(capped(50, 15_000, 100, 1_000).select(&FEES), 100);
+        // no overflow before the cap
+        assert_eq!(capped(100, u64::MAX, 0, 5_000).select(&[u64::MAX]), 5_000);
+    }
+}
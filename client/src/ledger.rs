@@ -0,0 +1,348 @@
+/*
+Append-only JSONL ledger of our fills, one file per market.
+
+Every line is a FillRecord carrying the running position after that fill, so the position
+is restored by reading the last line on startup. Inventory is tracked at average cost:
+fills that reduce the position realize (price - average cost) * size, fees are charged to
+realized PnL, and `edge_pnl` accumulates the fill price against the fair price at fill time.
+*/
+use crate::events::*;
+use crate::utils::ExecutedTransaction;
+use anyhow::{Context, Result};
+use phoenix_mm::conversions::*;
+use phoenix_mm::types::*;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FillRecord {
+    pub market: String,
+    pub signature: String,
+    pub slot: u64,
+    pub timestamp: i64,
+    /// "bid" if we bought, "ask" if we sold
+    pub side: String,
+    /// "maker" or "taker"
+    pub role: String,
+    pub order_sequence_number: u64,
+    pub price_in_ticks: u64,
+    pub price: f64,
+    pub base_lots: u64,
+    pub size: f64,
+    /// Fee paid in quote units
+    pub fee: f64,
+    pub fair_price: f64,
+    #[serde(flatten)]
+    pub position: Position,
+}
+
+/// Running position in UI units
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    /// Signed base inventory, negative when short
+    pub inventory: f64,
+    pub average_cost: f64,
+    pub realized_pnl: f64,
+    pub edge_pnl: f64,
+}
+impl Position {
+    pub fn apply(&mut self, side: Side, price: f64, size: f64, fee: f64, fair_price: f64) {
+        let signed_size = match side {
+            Side::Bid => size,
+            Side::Ask => -size,
+        };
+        // part of the fill that closes the current position
+        let closing = if self.inventory * signed_size < 0.0 {
+            size.min(self.inventory.abs())
+        } else {
+            0.0
+        };
+        if closing > 0.0 {
+            self.realized_pnl += (price - self.average_cost) * closing * self.inventory.signum();
+        }
+        let opening = size - closing;
+        let remaining = self.inventory + signed_size.signum() * closing;
+        if opening > 0.0 {
+            let new_inventory = remaining + signed_size.signum() * opening;
+            self.average_cost =
+                (self.average_cost * remaining.abs() + price * opening) / new_inventory.abs();
+            self.inventory = new_inventory;
+        } else {
+            self.inventory = remaining;
+            if self.inventory == 0.0 {
+                self.average_cost = 0.0;
+            }
+        }
+        self.realized_pnl -= fee;
+        self.edge_pnl += (fair_price - price) * signed_size;
+    }
+}
+
+pub struct Ledger {
+    market: String,
+    header: MarketHeader,
+    trader: Pubkey,
+    file: File,
+    position: Position,
+}
+impl Ledger {
+    /// Open (or create) `<dir>/<market>.jsonl` and restore the position from its last line
+    pub fn open(dir: &Path, market: &str, header: &MarketHeader, trader: Pubkey) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {:?}", dir))?;
+        let path = ledger_path(dir, market);
+        let mut position = Position::default();
+        if path.exists() {
+            let contents = std::fs::read(&path).with_context(|| format!("reading {:?}", path))?;
+            // a crash mid-write leaves an incomplete last line, drop it so appends start clean
+            let complete = contents
+                .iter()
+                .rposition(|&byte| byte == b'\n')
+                .map_or(0, |newline| newline + 1);
+            if complete < contents.len() {
+                tracing::warn!(
+                    path = ?path,
+                    bytes = contents.len() - complete,
+                    "truncating incomplete last ledger line"
+                );
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|file| file.set_len(complete as u64))
+                    .with_context(|| format!("truncating {:?}", path))?;
+            }
+            if let Some(line) = contents[..complete]
+                .split(|&byte| byte == b'\n')
+                .rfind(|line| !line.is_empty())
+            {
+                let record: FillRecord = serde_json::from_slice(line)
+                    .with_context(|| format!("parsing last line of {:?}", path))?;
+                position = record.position;
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("opening {:?}", path))?;
+        Ok(Ledger {
+            market: market.to_string(),
+            header: *header,
+            trader,
+            file,
+            position,
+        })
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Append our fills in `executed`, priced against `fair_price` (UI units)
+    pub fn record(
+        &mut self,
+        executed: &ExecutedTransaction,
+        fair_price: f64,
+    ) -> Result<Vec<FillRecord>> {
+        let trader = self.trader.to_bytes();
+        let mut records = vec![];
+        for batch in &executed.events {
+            let signed = batch.header.signer == trader;
+            // taker fees are only reported per order, in the FillSummary after its fills
+            let mut pending_taker_fills: Vec<(Side, FillEvent)> = vec![];
+            for event in &batch.events {
+                match event {
+                    PhoenixMarketEvent::Fill(fill) if fill.maker_id == trader => {
+                        let side = Side::from_order_sequence_number(fill.order_sequence_number);
+                        records
+                            .push(self.apply(executed, batch, side, "maker", fill, 0, fair_price));
+                    }
+                    PhoenixMarketEvent::Fill(fill) if signed => {
+                        let side =
+                            Side::from_order_sequence_number(fill.order_sequence_number).opposite();
+                        pending_taker_fills.push((side, *fill));
+                    }
+                    PhoenixMarketEvent::FillSummary(summary) if signed => {
+                        let total_lots: u64 = pending_taker_fills
+                            .iter()
+                            .map(|(_, f)| f.base_lots_filled)
+                            .sum();
+                        for (side, fill) in pending_taker_fills.drain(..) {
+                            let fee_in_quote_lots = (summary.total_fee_in_quote_lots as u128
+                                * fill.base_lots_filled as u128
+                                / total_lots.max(1) as u128)
+                                as u64;
+                            records.push(self.apply(
+                                executed,
+                                batch,
+                                side,
+                                "taker",
+                                &fill,
+                                fee_in_quote_lots,
+                                fair_price,
+                            ));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for record in records.iter() {
+            writeln!(self.file, "{}", serde_json::to_string(record)?)?;
+        }
+        self.file.flush()?;
+        Ok(records)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn apply(
+        &mut self,
+        executed: &ExecutedTransaction,
+        batch: &PhoenixEventBatch,
+        side: Side,
+        role: &str,
+        fill: &FillEvent,
+        fee_in_quote_lots: u64,
+        fair_price: f64,
+    ) -> FillRecord {
+        let price = ticks_to_ui_price(&self.header, fill.price_in_ticks);
+        let size = base_lots_to_ui_size(&self.header, fill.base_lots_filled);
+        let fee = quote_lots_to_ui_amount(&self.header, fee_in_quote_lots);
+        self.position.apply(side, price, size, fee, fair_price);
+        FillRecord {
+            market: self.market.clone(),
+            signature: executed.meta.signature.to_string(),
+            slot: batch.header.slot,
+            timestamp: batch.header.timestamp,
+            side: match side {
+                Side::Bid => "bid",
+                Side::Ask => "ask",
+            }
+            .to_string(),
+            role: role.to_string(),
+            order_sequence_number: fill.order_sequence_number,
+            price_in_ticks: fill.price_in_ticks,
+            price,
+            base_lots: fill.base_lots_filled,
+            size,
+            fee,
+            fair_price,
+            position: self.position,
+        }
+    }
+}
+
+/// Market names like "SOL/USDC" are not valid file names
+pub fn ledger_path(dir: &Path, market: &str) -> PathBuf {
+    let name: String = market
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    dir.join(format!("{}.jsonl", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn position_of(inventory: f64, average_cost: f64, realized_pnl: f64) -> Position {
+        Position {
+            inventory,
+            average_cost,
+            realized_pnl,
+            edge_pnl: 0.0,
+        }
+    }
+
+    #[test]
+    fn adding_weights_the_average_cost() {
+        let mut position = Position::default();
+        position.apply(Side::Bid, 100.0, 1.0, 0.0, 100.0);
+        position.apply(Side::Bid, 104.0, 3.0, 0.0, 104.0);
+        assert_eq!(position, position_of(4.0, 103.0, 0.0));
+        // same on the short side
+        let mut position = Position::default();
+        position.apply(Side::Ask, 100.0, 3.0, 0.0, 100.0);
+        position.apply(Side::Ask, 108.0, 1.0, 0.0, 108.0);
+        assert_eq!(position, position_of(-4.0, 102.0, 0.0));
+    }
+
+    #[test]
+    fn closing_realizes_the_closed_size() {
+        let mut position = position_of(4.0, 103.0, 0.0);
+        position.apply(Side::Ask, 110.0, 1.0, 0.0, 110.0);
+        assert_eq!(position, position_of(3.0, 103.0, 7.0));
+        position.apply(Side::Ask, 101.0, 3.0, 0.0, 101.0);
+        assert_eq!(position, position_of(0.0, 0.0, 1.0));
+        // a short gains when bought back lower
+        let mut position = position_of(-2.0, 90.0, 0.0);
+        position.apply(Side::Bid, 80.0, 1.0, 0.0, 80.0);
+        assert_eq!(position, position_of(-1.0, 90.0, 10.0));
+    }
+
+    #[test]
+    fn flipping_through_zero_restarts_at_the_fill_price() {
+        let mut position = position_of(3.0, 103.0, 0.0);
+        // 3 close at a loss of 13 each, 2 open a short at 90
+        position.apply(Side::Ask, 90.0, 5.0, 0.0, 90.0);
+        assert_eq!(position, position_of(-2.0, 90.0, -39.0));
+        position.apply(Side::Bid, 80.0, 6.0, 0.0, 80.0);
+        assert_eq!(position, position_of(4.0, 80.0, -19.0));
+    }
+
+    #[test]
+    fn fees_and_edge_accumulate() {
+        let mut position = Position::default();
+        // bought 2 below fair, sold 1 above fair
+        position.apply(Side::Bid, 99.0, 2.0, 0.5, 100.0);
+        position.apply(Side::Ask, 102.0, 1.0, 0.25, 101.0);
+        assert_eq!(
+            position,
+            Position {
+                inventory: 1.0,
+                average_cost: 99.0,
+                realized_pnl: 3.0 - 0.75,
+                edge_pnl: 2.0 + 1.0,
+            }
+        );
+    }
+
+    #[test]
+    fn open_drops_an_incomplete_last_line() {
+        let dir = std::env::temp_dir().join(format!("ledger-test-{}", std::process::id()));
+        let path = ledger_path(&dir, "SOL/USDC");
+        std::fs::create_dir_all(&dir).unwrap();
+        let record = FillRecord {
+            market: "SOL/USDC".to_string(),
+            signature: String::new(),
+            slot: 1,
+            timestamp: 0,
+            side: "bid".to_string(),
+            role: "maker".to_string(),
+            order_sequence_number: 1,
+            price_in_ticks: 100,
+            price: 100.0,
+            base_lots: 10,
+            size: 1.0,
+            fee: 0.0,
+            fair_price: 100.0,
+            position: Position {
+                inventory: 1.0,
+                average_cost: 100.0,
+                realized_pnl: 0.0,
+                edge_pnl: 0.0,
+            },
+        };
+        let complete = format!("{}\n", serde_json::to_string(&record).unwrap());
+        std::fs::write(&path, format!("{}{{\"market\":\"SOL", complete)).unwrap();
+
+        let ledger =
+            Ledger::open(&dir, "SOL/USDC", &MarketHeader::zeroed(), Pubkey::default()).unwrap();
+        assert_eq!(ledger.position(), record.position);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), complete);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
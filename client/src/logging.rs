@@ -0,0 +1,179 @@
+/*
+tracing setup and parsing of Solana program logs into structured events.
+
+Program logs come as lines such as
+
+  Program 6Rav... invoke [1]
+  Program log: Best Bid: 1873
+  Program 6Rav... consumed 21042 of 200000 compute units
+  Program 6Rav... success
+
+`Program log: <key>: <value>` lines become key/value events (key in snake_case), the rest
+keep their meaning (invoke depth, compute units, result).
+*/
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow!("unknown log format {:?}, expected text or json", s)),
+        }
+    }
+}
+
+/// Install the global subscriber, filtered by `RUST_LOG` (default `info`)
+pub fn init_logging(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Text => builder.init(),
+        // spans are flattened into each event so log shippers see market/slot on every line
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramLog<'a> {
+    Invoke {
+        program: &'a str,
+        depth: u32,
+    },
+    Consumed {
+        program: &'a str,
+        units: u64,
+        limit: u64,
+    },
+    Success {
+        program: &'a str,
+    },
+    Failed {
+        program: &'a str,
+        error: &'a str,
+    },
+    Return {
+        program: &'a str,
+        data: &'a str,
+    },
+    /// `Program log: <key>: <value>`
+    KeyValue {
+        key: String,
+        value: &'a str,
+    },
+    /// `Program log: <message>`
+    Message(&'a str),
+    Other(&'a str),
+}
+
+pub fn parse_program_log(line: &str) -> ProgramLog<'_> {
+    if let Some(message) = line.strip_prefix("Program log: ") {
+        return match message.split_once(": ") {
+            Some((key, value)) => ProgramLog::KeyValue {
+                key: snake_case(key),
+                value: value.trim(),
+            },
+            None => ProgramLog::Message(message.trim()),
+        };
+    }
+    if let Some(rest) = line.strip_prefix("Program return: ") {
+        if let Some((program, data)) = rest.split_once(' ') {
+            return ProgramLog::Return { program, data };
+        }
+    }
+    let Some(rest) = line.strip_prefix("Program ") else {
+        return ProgramLog::Other(line);
+    };
+    let Some((program, rest)) = rest.split_once(' ') else {
+        return ProgramLog::Other(line);
+    };
+    if rest == "success" {
+        return ProgramLog::Success { program };
+    }
+    if let Some(error) = rest.strip_prefix("failed: ") {
+        return ProgramLog::Failed { program, error };
+    }
+    if let Some(depth) = rest
+        .strip_prefix("invoke [")
+        .and_then(|d| d.strip_suffix(']'))
+        .and_then(|d| d.parse().ok())
+    {
+        return ProgramLog::Invoke { program, depth };
+    }
+    if let Some((units, limit)) = rest
+        .strip_prefix("consumed ")
+        .and_then(|r| r.strip_suffix(" compute units"))
+        .and_then(|r| r.split_once(" of "))
+    {
+        if let (Ok(units), Ok(limit)) = (units.parse(), limit.parse()) {
+            return ProgramLog::Consumed {
+                program,
+                units,
+                limit,
+            };
+        }
+    }
+    ProgramLog::Other(line)
+}
+
+/// Emit every program log line as a structured event
+pub fn trace_program_logs(logs: &[String]) {
+    for line in logs {
+        match parse_program_log(line) {
+            ProgramLog::KeyValue { key, value } => {
+                tracing::debug!(target: "program_log", key = %key, value, "program value")
+            }
+            ProgramLog::Message(message) => {
+                tracing::debug!(target: "program_log", message)
+            }
+            ProgramLog::Consumed {
+                program,
+                units,
+                limit,
+            } => {
+                tracing::debug!(target: "program_log", program, units, limit, "compute units")
+            }
+            ProgramLog::Failed { program, error } => {
+                tracing::warn!(target: "program_log", program, error, "program failed")
+            }
+            ProgramLog::Invoke { program, depth } => {
+                tracing::trace!(target: "program_log", program, depth, "invoke")
+            }
+            ProgramLog::Success { program } => {
+                tracing::trace!(target: "program_log", program, "success")
+            }
+            ProgramLog::Return { program, data } => {
+                tracing::trace!(target: "program_log", program, data, "return data")
+            }
+            ProgramLog::Other(line) => tracing::trace!(target: "program_log", line),
+        }
+    }
+}
+
+fn snake_case(key: &str) -> String {
+    key.trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
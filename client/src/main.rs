@@ -1,151 +1,479 @@
 #![allow(warnings)]
+pub mod accounts;
+pub mod config;
+pub mod events;
+pub mod fees;
+pub mod ledger;
+pub mod logging;
+pub mod metrics;
+pub mod price;
+pub mod quoter;
 pub mod utils;
-use std::{env, vec};
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+    vec,
+};
 
+use crate::accounts::*;
+use crate::config::*;
+use crate::events::*;
+use crate::fees::*;
+use crate::ledger::*;
+use crate::logging::*;
+use crate::metrics::*;
+use crate::price::*;
+use crate::quoter::*;
 use crate::utils::*;
 use dotenvy::dotenv;
 use litesvm::LiteSVM;
+use phoenix_mm::book::*;
+use phoenix_mm::conversions::*;
+use phoenix_mm::sdk::*;
 use phoenix_mm::types::*;
+use phoenix_mm::utils::*;
 use reqwest::{
     Client,
     header::{HeaderMap, HeaderValue},
 };
 use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_clock::Clock;
 use solana_rpc_client::http_sender::HttpSender;
-use solana_sdk::pubkey;
-use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey, system_program};
-use spl_associated_token_account::get_associated_token_address;
-const PROGRAM_ID: Pubkey = solana_sdk::pubkey!("6RavfKEf7qqJLXmmwUWVBkaN56pZ71JtqCFfS99bHrpu");
-const PHOENIX: Pubkey = pubkey!("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY");
-const PHOENIX_SEAT_MANAGER: Pubkey = pubkey!("PSMxQbAoDWDbvd9ezQJgARyq6R9L5kJAasaLDVcZwf1");
-const PHOENIX_LOG_AUTH: Pubkey = pubkey!("7aDTsspkQNGKmrexAN7FLx9oxU3iPczSSvHNggyuqYkR");
-const WALLET_PATH: &str = "/home/mubariz/wallnuts/mainnet-keypair.json";
-const WALLET: Pubkey = pubkey!("5BvrQfDzwjFFjpaAys2KA1a7GuuhLXKJoCWykhsoyHet"); //replace with your actual wallet
-
-const SOL_BALANCE: u64 = 1000 * 1_000_000_000; //hehehe
-const USDC_BALANCE: u64 = 10_000 * 1_000_000;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::{EncodableKey, Signer},
+};
+use tracing::Instrument;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let rpc_url = env::var("RPC_URL").unwrap();
-    let origin = env::var("ORIGIN_HEADER").unwrap();
+    let config = Config::load().unwrap();
+    init_logging(config.log_format);
     let price_fetch_client = Client::new();
     let mut headers = HeaderMap::new();
-    headers.insert("origin", HeaderValue::from_str(&origin).unwrap());
+    if let Some(origin) = &config.rpc.origin_header {
+        headers.insert("origin", HeaderValue::from_str(origin).unwrap());
+    }
     let req_client = reqwest::Client::builder()
         .default_headers(headers)
         .build()
         .unwrap();
-    let http_sender_mainnet = HttpSender::new_with_client(rpc_url, req_client);
-    let rpc = RpcClient::new_sender(http_sender_mainnet, RpcClientConfig::default());
+    let http_sender_mainnet = HttpSender::new_with_client(config.rpc.url.clone(), req_client);
+    let rpc = Arc::new(RpcClient::new_sender(
+        http_sender_mainnet,
+        RpcClientConfig::default(),
+    ));
+    let metrics = Arc::new(Metrics::new(config.markets.iter().map(|m| m.name.as_str())));
+    if let Some(addr) = config.metrics_addr {
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_metrics(addr, metrics).await {
+                tracing::error!(error = ?e, "metrics server stopped");
+            }
+        });
+    }
+    // every market runs its own loop against its own LiteSVM instance
+    let results = futures::future::join_all(config.markets.iter().map(|market| {
+        run_market(
+            &config,
+            market,
+            &rpc,
+            &price_fetch_client,
+            metrics.market(&market.name),
+        )
+    }))
+    .await;
+    for (market, result) in config.markets.iter().zip(results) {
+        if let Err(e) = result {
+            tracing::error!(market = %market.name, error = ?e, "market stopped");
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(market = %market_config.name))]
+async fn run_market(
+    config: &Config,
+    market_config: &MarketConfig,
+    rpc: &Arc<RpcClient>,
+    price_fetch_client: &Client,
+    metrics: Arc<MarketMetrics>,
+) -> anyhow::Result<()> {
+    let payer = Keypair::read_from_file(config.keypair_path(market_config)?)
+        .map_err(|e| anyhow::anyhow!("reading keypair: {}", e))?;
+    let wallet = payer.pubkey();
+    let market = market_config.address;
+    let price_source = price_source_from_config(&market_config.price, price_fetch_client, rpc);
     let mut litesvm = LiteSVM::new().with_blockhash_check(true);
-    let market = Pubkey::from_str_const("4DoNfFBfF7UokCC2FQzriy7yHK6DY6NVdYpuekQ5pRgg"); //phoenix sol-usdc pool
-    let base_mint = Pubkey::from_str_const("So11111111111111111111111111111111111111112"); //sol
-    let quote_mint = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"); //usdc
-    let base_account_address = get_associated_token_address(&WALLET, &base_mint);
-    let quote_account_address = get_associated_token_address(&WALLET, &quote_mint);
-    let base_account =
-        get_dummy_token_account(&litesvm, WALLET, base_mint, spl_token::id(), SOL_BALANCE).unwrap();
-    let quote_account =
-        get_dummy_token_account(&litesvm, WALLET, quote_mint, spl_token::id(), USDC_BALANCE)
-            .unwrap();
-    //can also be derived from market data
-    let base_vault = Pubkey::from_str_const("8g4Z9d6PqGkgH31tMW6FwxGhwYJrXpxZHQrkikpLJKrG");
-    let quote_vault = Pubkey::from_str_const("3HSYXeGc3LjEPCuzoNDjQN37F1ebsSiR4CqXVqQCdekZ");
-    //derive nexessary pda's
-    let strategy = Pubkey::find_program_address(
-        &[b"phoenix_strategy".as_ref(), WALLET.as_ref()],
-        &PROGRAM_ID,
-    )
-    .0;
-    let seat = Pubkey::find_program_address(
-        &[b"seat".as_ref(), market.as_ref(), WALLET.as_ref()],
-        &PHOENIX,
-    )
-    .0;
-    let seat_manager = Pubkey::find_program_address(&[market.as_ref()], &PHOENIX_SEAT_MANAGER).0;
-    let seat_deposit_collector = Pubkey::find_program_address(
-        &[market.as_ref(), b"deposit".as_ref()],
-        &PHOENIX_SEAT_MANAGER,
-    )
-    .0;
 
     // add necessary programs
-    litesvm.add_program_from_file(PROGRAM_ID, "../target/deploy/phoenix_mm.so");
-    litesvm.add_program_from_file(PHOENIX, "../phoenix.so");
-    litesvm.add_program_from_file(PHOENIX_SEAT_MANAGER, "../phoniex_seat_manager.so");
-    let market_account = add_seat_to_market(&litesvm, &rpc, market);
+    litesvm.add_program_from_file(PROGRAM_ID, &config.programs.phoenix_mm)?;
+    litesvm.add_program_from_file(PHOENIX_PROGRAM_ID, &config.programs.phoenix)?;
+    litesvm.add_program_from_file(
+        PHOENIX_SEAT_MANAGER_PROGRAM_ID,
+        &config.programs.seat_manager,
+    )?;
+    let market_account = add_seat_to_market(&litesvm, rpc, market, wallet);
+    let market_header =
+        deserialize_market_header(&market_account.data[..size_of::<MarketHeader>()])
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    {
+        let book = deserialize_market(&market_account.data, &market_header.market_size_params)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        let book = &*book;
+        tracing::info!(
+            taker_fee_bps = book.get_taker_fee_bps(),
+            sequence_number = book.get_sequence_number(),
+            collected_fees_in_quote_lots = book.get_collected_fee_amount().inner,
+            uncollected_fees_in_quote_lots = book.get_uncollected_fee_amount().inner,
+            bids = book.get_book(Side::Bid).len(),
+            asks = book.get_book(Side::Ask).len(),
+            "market stats"
+        );
+    }
+    // mints, vaults and pda's all come from the market header
+    let accounts = MarketAccounts::from_header(market, &market_header, wallet);
+    let base_account = get_dummy_token_account(
+        &litesvm,
+        wallet,
+        accounts.base_mint,
+        spl_token::id(),
+        (market_config.base_balance * 10f64.powi(market_header.base_params.decimals as i32)) as u64,
+    )?;
+    let quote_account = get_dummy_token_account(
+        &litesvm,
+        wallet,
+        accounts.quote_mint,
+        spl_token::id(),
+        ui_amount_to_quote_atoms(&market_header, market_config.quote_balance),
+    )?;
+
     //add seat account
-    litesvm.set_account(seat, create_seat(&litesvm, market, WALLET));
+    litesvm.set_account(
+        accounts.update_quotes.seat,
+        create_seat(&litesvm, market, wallet),
+    )?;
     //add market account
-    litesvm.set_account(market, market_account);
+    litesvm.set_account(market, market_account)?;
     //dummy token accounts
-    litesvm.set_account(base_account_address, base_account);
-    litesvm.set_account(quote_account_address, quote_account);
+    litesvm.set_account(accounts.update_quotes.base_account, base_account)?;
+    litesvm.set_account(accounts.update_quotes.quote_account, quote_account)?;
 
     // ---InitalizeInstruction---
     //inital config
+    let strategy_config = &market_config.strategy;
     let initalize_params = StrategyParams {
-        quote_edge_in_bps: 2,
-        quote_size_in_quote_atoms: 500 * 1_000_000,
-        price_improvement_behavior: 2,
-        post_only: 0,
-        padding: [0u8; 6],
+        quote_edge_in_bps: strategy_config.quote_edge_in_bps,
+        quote_size_in_quote_atoms: ui_amount_to_quote_atoms(
+            &market_header,
+            strategy_config.quote_size,
+        ),
+        price_improvement_behavior: strategy_config.price_improvement_behavior()?.to_u8(),
+        post_only: strategy_config.post_only as u8,
+        cover_taker_fee: strategy_config.cover_taker_fee as u8,
+        hedge_cost_in_bps: strategy_config.hedge_cost_in_bps,
+        requote_tolerance_in_ticks: strategy_config.requote_tolerance_in_ticks,
+        keep_remaining_in_pct: strategy_config.keep_remaining_in_pct,
+        min_best_size_in_pct: strategy_config.min_best_size_in_pct,
+        min_best_size_in_base_lots: strategy_config.min_best_size_in_base_lots,
+        best_price_depth_in_base_lots: strategy_config.best_price_depth_in_base_lots,
+        pricing_model: strategy_config.pricing_model()?.to_u8(),
+        risk_aversion_in_millionths: strategy_config.risk_aversion_in_millionths(),
+        order_arrival_decay_in_millionths: strategy_config.order_arrival_decay_in_millionths(),
+        volatility_in_bps: strategy_config.volatility_in_bps,
+        volatility_span_in_slots: strategy_config.volatility_span_in_slots,
+        volatility_edge_multiplier_in_hundredths: strategy_config
+            .volatility_edge_multiplier_in_hundredths(),
     };
     //necessary accounts for initalize ix
-    hydrate_with_mainnet(&rpc, &mut litesvm, vec![WALLET, market]);
-    let mut accounts = vec![
-        AccountMeta::new(strategy, false),
-        AccountMeta::new(WALLET, true),
-        AccountMeta::new_readonly(market, false),
-        AccountMeta::new_readonly(system_program::id(), false),
-    ];
-    let mut data: Vec<u8> = vec![0u8];
-    data.extend_from_slice(unsafe { to_bytes(&initalize_params, 24) });
-    execute_transaction(&mut litesvm, accounts, data, PROGRAM_ID).await;
+    hydrate_with_mainnet(rpc, &mut litesvm, vec![wallet, market]);
+    sync_clock_with_mainnet(rpc, &mut litesvm)?;
+    let budget = &config.compute_budget;
+    let mut priority_fee = budget.priority_fee.fetch(rpc, &[market])?;
+    execute_transaction(
+        &mut litesvm,
+        &payer,
+        initialize_ix(&wallet, &market, &initalize_params),
+        budget,
+        priority_fee,
+    )
+    .await?;
 
-    for i in 0..3 {
-        let price = get_price(&price_fetch_client).await;
-        println!("Update No: ${}", i);
-        println!("SOL/USD Price: ${}", price);
-        hydrate_with_mainnet(
-            &rpc,
-            &mut litesvm,
-            vec![
-                WALLET,
-                PHOENIX_LOG_AUTH,
-                strategy,
-                seat_manager,
-                seat_deposit_collector,
-                base_mint,
-                quote_mint,
-                base_vault,
-                quote_vault,
-            ],
+    let mut ledger = Ledger::open(
+        &config.ledger_dir,
+        &market_config.name,
+        &market_header,
+        wallet,
+    )?;
+    let quoter_config = QuoterConfig::from(&config.quoter);
+    let mut mirror = StrategyMirror::default();
+    let mut backoff = Backoff::new(quoter_config.update_interval, quoter_config.max_backoff);
+    let mut delay = Duration::ZERO;
+    let mut update_no: u64 = 0;
+    let started = Instant::now();
+    let mut last_cycle = started;
+    let mut quotes_live = false;
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => {
+                tracing::info!("shutting down, cancelling all orders");
+                if let Err(e) = execute_transaction(&mut litesvm, &payer, cancel_all_orders_ix(&accounts.update_quotes), budget, priority_fee).await {
+                    tracing::error!(error = ?e, "cancel all failed");
+                }
+                return Ok(());
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+        if quotes_live {
+            metrics
+                .quote_uptime_seconds
+                .add(last_cycle.elapsed().as_secs_f64());
+        }
+        last_cycle = Instant::now();
+        metrics.running_seconds.set(started.elapsed().as_secs_f64());
+        let span = tracing::info_span!(
+            "cycle",
+            update = update_no,
+            slot = tracing::field::Empty,
+            price = tracing::field::Empty,
+            fair_price = tracing::field::Empty,
+            signature = tracing::field::Empty,
+            bid = tracing::field::Empty,
+            ask = tracing::field::Empty,
+            result = tracing::field::Empty,
         );
-        //Note: I want the market data to be sycn with mainnet ,but my seat should be injected in it
-        //considering a simple case where market is owned by seat_manager and no eviction  needed
-        //add seat account
+        // Ok(true) when both quotes rest on the book after the cycle
+        let result: anyhow::Result<bool> = async {
+            let span = tracing::Span::current();
+            let fetch_started = Instant::now();
+            let price = price_source.fetch().await;
+            metrics
+                .price_feed_latency_seconds
+                .set(fetch_started.elapsed().as_secs_f64());
+            let price = price?.price;
+            let fair_price = ui_price_to_quote_atoms_per_raw_base_unit(&market_header, price);
+            span.record("price", price);
+            span.record("fair_price", fair_price);
+            hydrate_with_mainnet(rpc, &mut litesvm, accounts.hydrated_accounts());
+            // a strategy account created by an older program build is grown before its first use
+            if litesvm
+                .get_account(&accounts.update_quotes.strategy)
+                .is_some_and(|account| LEGACY_STRATEGY_STATE_SIZES.contains(&account.data.len()))
+            {
+                tracing::info!("migrating strategy account");
+                execute_transaction(
+                    &mut litesvm,
+                    &payer,
+                    migrate_strategy_ix(&wallet),
+                    budget,
+                    priority_fee,
+                )
+                .await?;
+            }
+            // follow the mainnet clock so quote age is measured in real slots and expiries by
+            // slot or timestamp are seen by both us and the program
+            let clock = sync_clock_with_mainnet(rpc, &mut litesvm)?;
+            let current_slot = clock.slot;
+            span.record("slot", current_slot);
+            let current_unix_timestamp = clock.unix_timestamp as u64;
+            // our orders only live in the local copy of the market
+            let local_market = litesvm
+                .get_account(&market)
+                .ok_or_else(|| anyhow::anyhow!("market account not found"))?;
+            let book = deserialize_market(&local_market.data, &market_header.market_size_params)
+                .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            let book = &*book;
+            let l2 = L2Book::<5>::from_market(book, None, current_slot, current_unix_timestamp);
+            tracing::debug!(
+                mid = l2.mid_price_in_ticks(),
+                microprice = l2.microprice_in_ticks(),
+                spread_bps = l2.spread_in_bps(),
+                bids = ?l2.bids,
+                asks = ?l2.asks,
+                "book"
+            );
+            if !mirror.needs_update(
+                &quoter_config,
+                fair_price,
+                current_slot,
+                current_unix_timestamp,
+                book,
+            ) {
+                span.record("result", "skipped");
+                let (bid, ask) = record_quote_metrics(
+                    &metrics,
+                    &litesvm,
+                    &market_header,
+                    market,
+                    wallet,
+                    &mirror,
+                )?;
+                metrics.last_successful_slot.set(current_slot as f64);
+                return Ok(bid.is_some() && ask.is_some());
+            }
+            //Note: I want the market data to be sycn with mainnet ,but my seat should be injected in it
+            //considering a simple case where market is owned by seat_manager and no eviction  needed
+            //add seat account
+            litesvm.set_account(market, add_seat_to_market(&litesvm, rpc, market, wallet))?;
+            tracing::debug!(
+                price_in_ticks = ui_price_to_ticks(&market_header, price),
+                "requoting"
+            );
+            // ---UpdateInstruction
+            // keep the last fee when the fee lookup fails rather than skipping the update
+            match budget.priority_fee.fetch(rpc, &[market]) {
+                Ok(fee) => priority_fee = fee,
+                Err(e) => tracing::warn!(error = ?e, "priority fee lookup failed"),
+            }
+            metrics.updates_attempted.inc();
+            let executed = execute_transaction(
+                &mut litesvm,
+                &payer,
+                update_quotes_ix(&accounts.update_quotes, fair_price, &initalize_params),
+                budget,
+                priority_fee,
+            )
+            .await
+            .inspect_err(|_| metrics.updates_failed.inc())?;
+            metrics
+                .compute_units_consumed
+                .set(executed.meta.compute_units_consumed as f64);
+            metrics
+                .compute_units_consumed_total
+                .add(executed.meta.compute_units_consumed);
+            for batch in &executed.events {
+                for event in batch.trader_events(&wallet) {
+                    tracing::debug!(?event, "phoenix event");
+                }
+            }
+            for fill in ledger.record(&executed, price)? {
+                tracing::info!(
+                    side = %fill.side,
+                    role = %fill.role,
+                    size = fill.size,
+                    price = fill.price,
+                    fee = fill.fee,
+                    inventory = fill.position.inventory,
+                    realized_pnl = fill.position.realized_pnl,
+                    "fill"
+                );
+            }
+            metrics.inventory.set(ledger.position().inventory);
+            mirror.refresh(&litesvm, &accounts.update_quotes.strategy, fair_price)?;
+            // a side kept by the program places nothing, the strategy account still points to it
+            let (bid, ask) =
+                record_quote_metrics(&metrics, &litesvm, &market_header, market, wallet, &mirror)?;
+            if let Some(bid) = bid {
+                span.record("bid", bid);
+            }
+            if let Some(ask) = ask {
+                span.record("ask", ask);
+            }
+            metrics.last_successful_slot.set(current_slot as f64);
+            update_no += 1;
+            span.record("result", "updated");
+            Ok(bid.is_some() && ask.is_some())
+        }
+        .instrument(span.clone())
+        .await;
+        quotes_live = matches!(result, Ok(true));
+        let result_ok = result.is_ok();
+        metrics.quotes_live.set(quotes_live as u8 as f64);
+        delay = match result {
+            Ok(_) => backoff.reset(),
+            Err(e) => {
+                let delay = backoff.next();
+                span.record("result", "failed");
+                span.in_scope(|| tracing::warn!(error = ?e, retry_in = ?delay, "cycle failed"));
+                delay
+            }
+        };
+        if result_ok {
+            span.in_scope(|| tracing::info!("cycle done"));
+        }
+    }
+}
 
-        litesvm.set_account(market, add_seat_to_market(&litesvm, &rpc, market));
-        // ---UpdateInstruction
-        accounts = vec![
-            AccountMeta::new(strategy, false),
-            AccountMeta::new(market, false),
-            AccountMeta::new(WALLET, true),
-            AccountMeta::new_readonly(PHOENIX, false),
-            AccountMeta::new_readonly(PHOENIX_LOG_AUTH, false),
-            AccountMeta::new(seat, false),
-            AccountMeta::new(base_account_address, false),
-            AccountMeta::new(quote_account_address, false),
-            AccountMeta::new(base_vault, false),
-            AccountMeta::new(quote_vault, false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-        ];
-        data = vec![1u8];
-        data.extend_from_slice(&(price * 1_000_000u64).to_le_bytes());
-        data.extend_from_slice(unsafe { to_bytes(&initalize_params, 24) });
-        execute_transaction(&mut litesvm, accounts, data, PROGRAM_ID).await;
+// where our orders sit in the queue of their price level
+fn log_own_orders(
+    book: &dyn Market<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    trader_index: u64,
+    clock: &Clock,
+) {
+    let (slot, unix_timestamp) = (clock.slot, clock.unix_timestamp as u64);
+    for side in [Side::Bid, Side::Ask] {
+        for order in
+            l3_orders(book, side, slot, unix_timestamp).filter(|o| o.trader_index == trader_index)
+        {
+            let base_lots_ahead =
+                base_lots_ahead(book, side, &order.order_id, slot, unix_timestamp);
+            let competitor_base_lots = base_lots_at_price(
+                book,
+                side,
+                order.price_in_ticks(),
+                trader_index,
+                slot,
+                unix_timestamp,
+            );
+            tracing::debug!(
+                side = ?side,
+                price_in_ticks = order.price_in_ticks(),
+                size_in_base_lots = order.size_in_base_lots,
+                order_sequence_number = order.order_id.order_sequence_number,
+                base_lots_ahead,
+                competitor_base_lots,
+                "own order"
+            );
+        }
     }
 }
+
+// our resting quotes and their distance to the best quotes of other traders, NaN for a
+// missing side; returns the prices of the resting quotes
+fn record_quote_metrics(
+    metrics: &MarketMetrics,
+    litesvm: &LiteSVM,
+    header: &MarketHeader,
+    market: Pubkey,
+    wallet: Pubkey,
+    mirror: &StrategyMirror,
+) -> anyhow::Result<(Option<u64>, Option<u64>)> {
+    let account = litesvm
+        .get_account(&market)
+        .ok_or_else(|| anyhow::anyhow!("market account not found"))?;
+    let book = deserialize_market(&account.data, &header.market_size_params)
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    let book = &*book;
+    let trader_index = book
+        .get_trader_index(&wallet.to_bytes())
+        .ok_or_else(|| anyhow::anyhow!("trader has no seat on the market"))?;
+    let clock = litesvm.get_sysvar::<Clock>();
+    let (best_bid, best_ask) = get_best_bid_and_ask(
+        book,
+        trader_index as u64,
+        clock.slot,
+        clock.unix_timestamp as u64,
+        &BestPriceFilter::default(),
+    );
+    log_own_orders(book, trader_index as u64, &clock);
+    let (bid, ask) = mirror.resting_prices(book, clock.slot, clock.unix_timestamp as u64);
+    metrics
+        .bid_price_in_ticks
+        .set(bid.map_or(f64::NAN, |bid| bid as f64));
+    metrics
+        .best_bid_distance_in_ticks
+        .set(bid.map_or(f64::NAN, |bid| best_bid as f64 - bid as f64));
+    metrics
+        .ask_price_in_ticks
+        .set(ask.map_or(f64::NAN, |ask| ask as f64));
+    // u64::MAX means the ask side has no other orders
+    metrics.best_ask_distance_in_ticks.set(match ask {
+        Some(ask) if best_ask != u64::MAX => ask as f64 - best_ask as f64,
+        _ => f64::NAN,
+    });
+    Ok((bid, ask))
+}
@@ -0,0 +1,214 @@
+/*
+Prometheus metrics of the quoting loops, served as text on `GET /metrics`.
+
+Each market owns a MarketMetrics updated lock free from its loop; the server only reads
+them. Values are labelled with the market name.
+*/
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Monotonic u64 counter
+#[derive(Default)]
+pub struct Counter(AtomicU64);
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// f64 gauge stored as bits
+#[derive(Default)]
+pub struct Gauge(AtomicU64);
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+    pub fn add(&self, value: f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Default)]
+pub struct MarketMetrics {
+    pub updates_attempted: Counter,
+    pub updates_failed: Counter,
+    pub compute_units_consumed: Gauge,
+    pub compute_units_consumed_total: Counter,
+    pub bid_price_in_ticks: Gauge,
+    pub ask_price_in_ticks: Gauge,
+    /// Best other bid minus our bid, negative when we improve the book
+    pub best_bid_distance_in_ticks: Gauge,
+    /// Our ask minus the best other ask, negative when we improve the book
+    pub best_ask_distance_in_ticks: Gauge,
+    pub inventory: Gauge,
+    pub quotes_live: Gauge,
+    pub quote_uptime_seconds: Gauge,
+    pub running_seconds: Gauge,
+    pub price_feed_latency_seconds: Gauge,
+    pub last_successful_slot: Gauge,
+}
+
+pub struct Metrics {
+    markets: Vec<(String, Arc<MarketMetrics>)>,
+}
+impl Metrics {
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        Metrics {
+            markets: names
+                .into_iter()
+                .map(|name| (name.to_string(), Arc::new(MarketMetrics::default())))
+                .collect(),
+        }
+    }
+
+    pub fn market(&self, name: &str) -> Arc<MarketMetrics> {
+        self.markets
+            .iter()
+            .find(|(market, _)| market == name)
+            .map(|(_, metrics)| metrics.clone())
+            .expect("metrics are registered for every configured market")
+    }
+
+    /// Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters: [(&str, &str, fn(&MarketMetrics) -> u64); 3] = [
+            (
+                "updates_attempted_total",
+                "update_quotes transactions sent",
+                |m| m.updates_attempted.get(),
+            ),
+            (
+                "updates_failed_total",
+                "update_quotes transactions that failed",
+                |m| m.updates_failed.get(),
+            ),
+            (
+                "compute_units_consumed_total",
+                "Compute units consumed by update_quotes",
+                |m| m.compute_units_consumed_total.get(),
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP phoenix_mm_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE phoenix_mm_{} counter", name);
+            for (market, metrics) in &self.markets {
+                let _ = writeln!(
+                    out,
+                    "phoenix_mm_{}{{market=\"{}\"}} {}",
+                    name,
+                    market,
+                    value(metrics)
+                );
+            }
+        }
+        let gauges: [(&str, &str, fn(&MarketMetrics) -> f64); 11] = [
+            (
+                "compute_units_consumed",
+                "Compute units of the last update_quotes",
+                |m| m.compute_units_consumed.get(),
+            ),
+            ("bid_price_in_ticks", "Our bid price", |m| {
+                m.bid_price_in_ticks.get()
+            }),
+            ("ask_price_in_ticks", "Our ask price", |m| {
+                m.ask_price_in_ticks.get()
+            }),
+            (
+                "best_bid_distance_in_ticks",
+                "Best other bid minus our bid",
+                |m| m.best_bid_distance_in_ticks.get(),
+            ),
+            (
+                "best_ask_distance_in_ticks",
+                "Our ask minus the best other ask",
+                |m| m.best_ask_distance_in_ticks.get(),
+            ),
+            ("inventory", "Base inventory from the fill ledger", |m| {
+                m.inventory.get()
+            }),
+            ("quotes_live", "1 while both quotes rest on the book", |m| {
+                m.quotes_live.get()
+            }),
+            (
+                "quote_uptime_seconds",
+                "Time spent with both quotes on the book",
+                |m| m.quote_uptime_seconds.get(),
+            ),
+            (
+                "running_seconds",
+                "Time since the market loop started",
+                |m| m.running_seconds.get(),
+            ),
+            (
+                "price_feed_latency_seconds",
+                "Duration of the last price fetch",
+                |m| m.price_feed_latency_seconds.get(),
+            ),
+            (
+                "last_successful_slot",
+                "Slot of the last successful cycle",
+                |m| m.last_successful_slot.get(),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP phoenix_mm_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE phoenix_mm_{} gauge", name);
+            for (market, metrics) in &self.markets {
+                let _ = writeln!(
+                    out,
+                    "phoenix_mm_{}{{market=\"{}\"}} {}",
+                    name,
+                    market,
+                    value(metrics)
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Serve `GET /metrics` on `addr` until the process exits
+pub async fn serve_metrics(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("serving metrics on http://{}/metrics", addr);
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut request = [0u8; 1024];
+            let Ok(len) = stream.read(&mut request).await else {
+                return;
+            };
+            let response = if request[..len].starts_with(b"GET /metrics ") {
+                let body = metrics.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into()
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
@@ -0,0 +1,317 @@
+use crate::config::{PriceConfig, PriceSourceConfig};
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A price observation in UI units (e.g. USD per SOL)
+#[derive(Debug, Clone)]
+pub struct PriceQuote {
+    pub price: f64,
+    /// Unix timestamp (seconds) the price refers to
+    pub timestamp: i64,
+    pub source: String,
+}
+
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &str;
+    async fn fetch(&self) -> Result<PriceQuote>;
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+//Coin base api structure
+#[derive(Deserialize, Debug)]
+pub struct PriceData {
+    pub data: PriceInner,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PriceInner {
+    pub amount: String,
+    base: String,
+    currency: String,
+}
+
+/// Coinbase spot price, e.g. `SOL-USD`
+pub struct CoinbaseSource {
+    pub client: Client,
+    pub product: String,
+}
+#[async_trait]
+impl PriceSource for CoinbaseSource {
+    fn name(&self) -> &str {
+        "coinbase"
+    }
+    async fn fetch(&self) -> Result<PriceQuote> {
+        let resp = self
+            .client
+            .get(format!(
+                "https://api.coinbase.com/v2/prices/{}/spot",
+                self.product
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PriceData>()
+            .await?;
+        Ok(PriceQuote {
+            price: resp.data.amount.parse()?,
+            timestamp: now_unix(),
+            source: self.name().to_string(),
+        })
+    }
+}
+
+//Binance api structure
+#[derive(Deserialize, Debug)]
+pub struct BinanceTicker {
+    pub symbol: String,
+    pub price: String,
+}
+
+/// Binance last traded price, e.g. `SOLUSDT`
+pub struct BinanceSource {
+    pub client: Client,
+    pub symbol: String,
+}
+#[async_trait]
+impl PriceSource for BinanceSource {
+    fn name(&self) -> &str {
+        "binance"
+    }
+    async fn fetch(&self) -> Result<PriceQuote> {
+        let resp = self
+            .client
+            .get("https://api.binance.com/api/v3/ticker/price")
+            .query(&[("symbol", &self.symbol)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<BinanceTicker>()
+            .await?;
+        Ok(PriceQuote {
+            price: resp.price.parse()?,
+            timestamp: now_unix(),
+            source: self.name().to_string(),
+        })
+    }
+}
+
+/// Pyth `PriceUpdateV2` account (pull oracle) read over RPC
+pub struct PythSource {
+    pub rpc: Arc<RpcClient>,
+    pub price_account: Pubkey,
+}
+impl PythSource {
+    /// discriminator (8) + write_authority (32)
+    const VERIFICATION_LEVEL_OFFSET: usize = 40;
+
+    pub fn parse_price_update(data: &[u8]) -> Result<(f64, i64)> {
+        let mut offset = Self::VERIFICATION_LEVEL_OFFSET;
+        // VerificationLevel::Partial { num_signatures: u8 } | VerificationLevel::Full
+        offset += match data.get(offset) {
+            Some(0) => 2,
+            Some(1) => 1,
+            _ => bail!("invalid pyth verification level"),
+        };
+        offset += 32; // feed_id
+        let read = |at: usize, len: usize| -> Result<&[u8]> {
+            data.get(at..at + len)
+                .ok_or_else(|| anyhow!("pyth price account too short"))
+        };
+        let price = i64::from_le_bytes(read(offset, 8)?.try_into()?);
+        let exponent = i32::from_le_bytes(read(offset + 16, 4)?.try_into()?);
+        let publish_time = i64::from_le_bytes(read(offset + 20, 8)?.try_into()?);
+        Ok((price as f64 * 10f64.powi(exponent), publish_time))
+    }
+}
+#[async_trait]
+impl PriceSource for PythSource {
+    fn name(&self) -> &str {
+        "pyth"
+    }
+    async fn fetch(&self) -> Result<PriceQuote> {
+        // the shared RpcClient is blocking, keep it off the runtime threads
+        let (rpc, price_account) = (self.rpc.clone(), self.price_account);
+        let account =
+            tokio::task::spawn_blocking(move || rpc.get_account(&price_account)).await??;
+        let (price, timestamp) = Self::parse_price_update(&account.data)?;
+        Ok(PriceQuote {
+            price,
+            timestamp,
+            source: self.name().to_string(),
+        })
+    }
+}
+
+/// Fixed price, for tests and dry runs
+pub struct StaticSource {
+    pub price: f64,
+}
+#[async_trait]
+impl PriceSource for StaticSource {
+    fn name(&self) -> &str {
+        "static"
+    }
+    async fn fetch(&self) -> Result<PriceQuote> {
+        Ok(PriceQuote {
+            price: self.price,
+            timestamp: now_unix(),
+            source: self.name().to_string(),
+        })
+    }
+}
+
+/// Price read from a text file on every fetch, timestamped with the file mtime
+pub struct FileSource {
+    pub path: PathBuf,
+}
+#[async_trait]
+impl PriceSource for FileSource {
+    fn name(&self) -> &str {
+        "file"
+    }
+    async fn fetch(&self) -> Result<PriceQuote> {
+        let contents = tokio::fs::read_to_string(&self.path).await?;
+        let modified = tokio::fs::metadata(&self.path).await?.modified()?;
+        Ok(PriceQuote {
+            price: contents.trim().parse()?,
+            timestamp: modified.duration_since(UNIX_EPOCH)?.as_secs() as i64,
+            source: self.name().to_string(),
+        })
+    }
+}
+
+/// Median of several sources, dropping failed, stale and outlier quotes
+pub struct MedianAggregator {
+    pub sources: Vec<Box<dyn PriceSource>>,
+    /// Quotes older than this are ignored
+    pub max_age: Duration,
+    /// Quotes further than this from the median are ignored
+    pub max_deviation_in_bps: u64,
+    /// Minimum number of quotes left after filtering
+    pub min_sources: usize,
+}
+#[async_trait]
+impl PriceSource for MedianAggregator {
+    fn name(&self) -> &str {
+        "median"
+    }
+    async fn fetch(&self) -> Result<PriceQuote> {
+        let now = now_unix();
+        let results =
+            futures::future::join_all(self.sources.iter().map(|source| source.fetch())).await;
+        let fresh = results
+            .into_iter()
+            .zip(self.sources.iter())
+            .filter_map(|(result, source)| match result {
+                Ok(quote) if now - quote.timestamp <= self.max_age.as_secs() as i64 => Some(quote),
+                Ok(quote) => {
+                    tracing::warn!(
+                        source = source.name(),
+                        timestamp = quote.timestamp,
+                        "stale price"
+                    );
+                    None
+                }
+                Err(e) => {
+                    tracing::warn!(source = source.name(), error = ?e, "price fetch failed");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let quote = aggregate_median(fresh, self.max_deviation_in_bps, self.min_sources)?;
+        Ok(PriceQuote {
+            source: self.name().to_string(),
+            ..quote
+        })
+    }
+}
+
+/// Median after rejecting quotes more than `max_deviation_in_bps` away from the raw median
+pub fn aggregate_median(
+    quotes: Vec<PriceQuote>,
+    max_deviation_in_bps: u64,
+    min_sources: usize,
+) -> Result<PriceQuote> {
+    let raw_median = median(&quotes).ok_or_else(|| anyhow!("no price quotes"))?;
+    let accepted = quotes
+        .into_iter()
+        .filter(|q| {
+            (q.price - raw_median).abs() / raw_median * 10_000.0 <= max_deviation_in_bps as f64
+        })
+        .collect::<Vec<_>>();
+    if accepted.len() < min_sources.max(1) {
+        bail!(
+            "only {} price quotes left after filtering, need {}",
+            accepted.len(),
+            min_sources
+        );
+    }
+    Ok(PriceQuote {
+        price: median(&accepted).unwrap(),
+        timestamp: accepted.iter().map(|q| q.timestamp).min().unwrap(),
+        source: String::new(),
+    })
+}
+
+fn median(quotes: &[PriceQuote]) -> Option<f64> {
+    let mut prices = quotes.iter().map(|q| q.price).collect::<Vec<_>>();
+    prices.sort_by(f64::total_cmp);
+    let mid = prices.len() / 2;
+    match prices.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((prices[mid - 1] + prices[mid]) / 2.0),
+        _ => Some(prices[mid]),
+    }
+}
+
+/// Build the aggregated feed of a market from its `[markets.price]` section
+pub fn price_source_from_config(
+    config: &PriceConfig,
+    client: &Client,
+    rpc: &Arc<RpcClient>,
+) -> MedianAggregator {
+    let sources = config
+        .sources
+        .iter()
+        .map(|source| -> Box<dyn PriceSource> {
+            match source {
+                PriceSourceConfig::Coinbase { product } => Box::new(CoinbaseSource {
+                    client: client.clone(),
+                    product: product.clone(),
+                }),
+                PriceSourceConfig::Binance { symbol } => Box::new(BinanceSource {
+                    client: client.clone(),
+                    symbol: symbol.clone(),
+                }),
+                PriceSourceConfig::Pyth { account } => Box::new(PythSource {
+                    rpc: rpc.clone(),
+                    price_account: *account,
+                }),
+                PriceSourceConfig::File { path } => Box::new(FileSource { path: path.clone() }),
+                PriceSourceConfig::Static { price } => Box::new(StaticSource { price: *price }),
+            }
+        })
+        .collect();
+    MedianAggregator {
+        sources,
+        max_age: Duration::from_secs(config.max_age_secs),
+        max_deviation_in_bps: config.max_deviation_bps,
+        min_sources: config.min_sources,
+    }
+}
@@ -0,0 +1,151 @@
+use crate::*;
+use anyhow::{Result, anyhow};
+use phoenix_mm::types::*;
+use phoenix_mm::utils::*;
+use std::time::Duration;
+
+/// Cadence of the quoting loop
+#[derive(Debug, Clone)]
+pub struct QuoterConfig {
+    /// Delay between two update cycles
+    pub update_interval: Duration,
+    /// Minimum fair price move (in bps) that forces a requote
+    pub price_threshold_in_bps: u64,
+    /// Quotes older than this are refreshed even if the price did not move
+    pub max_quote_age_in_slots: u64,
+    /// Upper bound of the delay after consecutive failures
+    pub max_backoff: Duration,
+}
+
+/// Exponential backoff starting at the update interval
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    current: Duration,
+}
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Backoff {
+            base,
+            max,
+            current: base,
+        }
+    }
+    /// Delay to wait after a successful cycle
+    pub fn reset(&mut self) -> Duration {
+        self.current = self.base;
+        self.base
+    }
+    /// Delay to wait after a failed cycle, doubling up to `max`
+    pub fn next(&mut self) -> Duration {
+        self.current = (self.current * 2).min(self.max);
+        self.current
+    }
+}
+
+/// Local copy of the on-chain strategy account, plus the fair price we last quoted around
+#[derive(Default)]
+pub struct StrategyMirror {
+    pub state: Option<PhoenixStrategyState>,
+    pub last_fair_price: Option<u64>,
+}
+impl StrategyMirror {
+    /// Re-read the strategy account after an update
+    pub fn refresh(&mut self, litesvm: &LiteSVM, strategy: &Pubkey, fair_price: u64) -> Result<()> {
+        let account = litesvm
+            .get_account(strategy)
+            .ok_or_else(|| anyhow!("strategy account {} not found", strategy))?;
+        let state = bytemuck::try_from_bytes::<PhoenixStrategyState>(&account.data)
+            .map_err(|e| anyhow!("invalid strategy account: {:?}", e))?;
+        self.state = Some(*state);
+        self.last_fair_price = Some(fair_price);
+        Ok(())
+    }
+
+    /// Decide if an update_quotes is needed for the new fair price
+    pub fn needs_update(
+        &self,
+        config: &QuoterConfig,
+        fair_price: u64,
+        current_slot: u64,
+        current_unix_timestamp: u64,
+        market: &dyn Market<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    ) -> bool {
+        let (Some(state), Some(last_fair_price)) = (self.state, self.last_fair_price) else {
+            return true;
+        };
+        if price_moved_in_bps(last_fair_price, fair_price) >= config.price_threshold_in_bps {
+            return true;
+        }
+        if current_slot.saturating_sub(state.last_update_slot) > config.max_quote_age_in_slots {
+            return true;
+        }
+        !quotes_are_resting(&state, market, current_slot, current_unix_timestamp)
+    }
+
+    /// Prices of our bid and ask still resting on the book, partially filled or not
+    pub fn resting_prices(
+        &self,
+        market: &dyn Market<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket>,
+        current_slot: u64,
+        current_unix_timestamp: u64,
+    ) -> (Option<u64>, Option<u64>) {
+        let Some(state) = self.state else {
+            return (None, None);
+        };
+        let [bid, ask] = quotes(&state).map(|(side, order_id, _)| {
+            (order_id.order_sequence_number != 0
+                && market
+                    .get_book(side)
+                    .get(&order_id)
+                    .is_some_and(|order| !order.is_expired(current_slot, current_unix_timestamp)))
+            .then_some(order_id.price_in_ticks.inner)
+        });
+        (bid, ask)
+    }
+}
+
+pub fn price_moved_in_bps(previous: u64, current: u64) -> u64 {
+    if previous == 0 {
+        return u64::MAX;
+    }
+    (previous.abs_diff(current) as u128 * 10_000 / previous as u128) as u64
+}
+
+// our last bid and ask with the size they were placed with
+fn quotes(state: &PhoenixStrategyState) -> [(Side, FIFOOrderId, u64); 2] {
+    [
+        (
+            Side::Bid,
+            FIFOOrderId::new_from_untyped(
+                state.bid_price_in_ticks,
+                state.bid_order_sequence_number,
+            ),
+            state.initial_bid_size_in_base_lots,
+        ),
+        (
+            Side::Ask,
+            FIFOOrderId::new_from_untyped(
+                state.ask_price_in_ticks,
+                state.ask_order_sequence_number,
+            ),
+            state.initial_ask_size_in_base_lots,
+        ),
+    ]
+}
+
+// both of our orders are still on the book with their full size and not expired
+fn quotes_are_resting(
+    state: &PhoenixStrategyState,
+    market: &dyn Market<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    current_slot: u64,
+    current_unix_timestamp: u64,
+) -> bool {
+    quotes(state).iter().all(|(side, order_id, initial_size)| {
+        order_id.order_sequence_number != 0
+            && market.get_book(*side).get(order_id).is_some_and(|order| {
+                order.num_base_lots == *initial_size
+                    && !order.is_expired(current_slot, current_unix_timestamp)
+            })
+    })
+}
@@ -1,41 +1,26 @@
 use crate::*;
 use anyhow::{Error, Result};
 use litesvm::LiteSVM;
+use litesvm::types::TransactionMetadata;
+use phoenix_mm::sdk::*;
 use phoenix_mm::types::*;
 use phoenix_mm::utils::*;
-use reqwest::Client;
-use serde::Deserialize;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
     account::Account,
+    compute_budget::ComputeBudgetInstruction,
     instruction::{AccountMeta, Instruction},
     keccak,
     message::v0::Message,
     program_pack::Pack,
     pubkey::Pubkey,
     signature::Keypair,
-    signer::EncodableKey,
+    signer::{EncodableKey, Signer},
     system_program,
     transaction::VersionedTransaction,
 };
 
 use spl_token::state::Account as TokenAccount;
-
-//Coin base api structure
-#[derive(Deserialize, Debug)]
-pub struct PriceData {
-    pub data: PriceInner,
-}
-
-#[derive(Deserialize, Debug)]
-pub struct PriceInner {
-    pub amount: String,
-    base: String,
-    currency: String,
-}
-pub unsafe fn to_bytes<T>(data: &T, len: usize) -> &[u8] {
-    core::slice::from_raw_parts(data as *const T as *const u8, len)
-}
 pub fn get_dummy_token_account(
     svm: &LiteSVM,
     owner: Pubkey,
@@ -75,45 +60,112 @@ pub fn hydrate_with_mainnet(rpc: &RpcClient, litesvm: &mut LiteSVM, addresses: V
         }
     }
 }
+/// Copy the mainnet Clock sysvar into LiteSVM. `warp_to_slot` only moves the slot, orders that
+/// expire by unix timestamp need the real time as well
+pub fn sync_clock_with_mainnet(rpc: &RpcClient, litesvm: &mut LiteSVM) -> Result<Clock> {
+    let account = rpc.get_account(&solana_sdk::sysvar::clock::id())?;
+    let clock = solana_sdk::account::from_account::<Clock, _>(&account)
+        .ok_or_else(|| anyhow::anyhow!("invalid clock sysvar account"))?;
+    litesvm.set_sysvar(&clock);
+    Ok(clock)
+}
+/// A landed transaction with the Phoenix events it emitted
+pub struct ExecutedTransaction {
+    pub meta: TransactionMetadata,
+    pub events: Vec<PhoenixEventBatch>,
+    pub compute_unit_limit: u32,
+    /// Micro lamports per CU
+    pub priority_fee: u64,
+}
+fn sign_transaction(
+    litesvm: &LiteSVM,
+    payer: &Keypair,
+    ixs: &[Instruction],
+) -> VersionedTransaction {
+    let message =
+        Message::try_compile(&payer.pubkey(), ixs, &[], litesvm.latest_blockhash()).unwrap();
+    VersionedTransaction::try_new(solana_sdk::message::VersionedMessage::V0(message), &[payer])
+        .unwrap()
+}
+/// Simulate `ix`, then send it with a CU limit sized from the simulation and `priority_fee`
 pub async fn execute_transaction(
     litesvm: &mut LiteSVM,
-    accounts: Vec<AccountMeta>,
-    data: Vec<u8>,
-    program_id: Pubkey,
-) -> anyhow::Result<()> {
-    let payer = Keypair::read_from_file(WALLET_PATH).unwrap();
-    let ix = Instruction {
-        program_id: program_id,
-        accounts,
-        data,
+    payer: &Keypair,
+    ix: Instruction,
+    budget: &ComputeBudgetConfig,
+    priority_fee: u64,
+) -> anyhow::Result<ExecutedTransaction> {
+    // simulate under the highest allowed limit so the simulation itself is not cut short
+    let simulation = litesvm.simulate_transaction(sign_transaction(
+        litesvm,
+        payer,
+        &[
+            ComputeBudgetInstruction::set_compute_unit_limit(budget.max_cu_limit),
+            ix.clone(),
+        ],
+    ));
+    let simulated_units = match simulation {
+        Ok(simulated) => simulated.meta.compute_units_consumed,
+        Err(failed) => {
+            trace_program_logs(&failed.meta.logs);
+            return Err(anyhow::anyhow!("simulation failed: {:?}", failed.err));
+        }
     };
-    let blockhash = litesvm.latest_blockhash();
-    let message = Message::try_compile(&WALLET, &[ix], &[], blockhash).unwrap();
-    let tx =
-        VersionedTransaction::try_new(solana_sdk::message::VersionedMessage::V0(message), &[payer])
-            .unwrap();
+    let compute_unit_limit = budget.compute_unit_limit(simulated_units);
+    let tx = sign_transaction(
+        litesvm,
+        payer,
+        &[
+            ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit),
+            ComputeBudgetInstruction::set_compute_unit_price(priority_fee),
+            ix,
+        ],
+    );
 
-    println!("BlockHash : {:#?}", blockhash);
-    println!("Signature : {:#?}", tx.signatures[0]);
-    let reuslt = litesvm.send_transaction(tx).unwrap();
-    println!("{:#?}", reuslt.logs);
+    let signature = tx.signatures[0];
+    tracing::Span::current().record("signature", tracing::field::display(&signature));
+    tracing::debug!(
+        %signature,
+        simulated_units,
+        compute_unit_limit,
+        priority_fee,
+        "sending transaction"
+    );
+    let account_keys = tx.message.static_account_keys().to_vec();
+    let reuslt = litesvm.send_transaction(tx);
     litesvm.expire_blockhash();
-    Ok(())
-}
-//hardcoded for sol/usdc for now
-pub async fn get_price(client: &Client) -> u64 {
-    let resp = client
-        .get("https://api.coinbase.com/v2/prices/SOL-USD/spot")
-        .send()
-        .await
-        .unwrap()
-        .json::<PriceData>()
-        .await
-        .unwrap();
-    let price_f64: f64 = resp.data.amount.parse().unwrap();
-    (price_f64).round() as u64
+    match reuslt {
+        Ok(meta) => {
+            trace_program_logs(&meta.logs);
+            tracing::debug!(
+                %signature,
+                compute_units = meta.compute_units_consumed,
+                "transaction landed"
+            );
+            // a decode failure must not turn a landed transaction into an error
+            let events = decode_litesvm_events(&meta, &account_keys).unwrap_or_else(|e| {
+                tracing::warn!(error = ?e, "failed to decode phoenix events");
+                vec![]
+            });
+            Ok(ExecutedTransaction {
+                meta,
+                events,
+                compute_unit_limit,
+                priority_fee,
+            })
+        }
+        Err(failed) => {
+            trace_program_logs(&failed.meta.logs);
+            Err(anyhow::anyhow!("transaction failed: {:?}", failed.err))
+        }
+    }
 }
-pub fn add_seat_to_market(litesvm: &LiteSVM, rpc: &RpcClient, market: Pubkey) -> Account {
+pub fn add_seat_to_market(
+    litesvm: &LiteSVM,
+    rpc: &RpcClient,
+    market: Pubkey,
+    trader: Pubkey,
+) -> Account {
     let mainnet_market_account = rpc.get_account(&market).unwrap();
     let mut bytes = mainnet_market_account.data;
     let (market_header_bytes, _) = bytes.split_at_mut(size_of::<MarketHeader>());
@@ -121,7 +173,7 @@ pub fn add_seat_to_market(litesvm: &LiteSVM, rpc: &RpcClient, market: Pubkey) ->
         .unwrap()
         .market_size_params;
     let market = deserialize_market_mut(&mut bytes, &market_size_params).unwrap();
-    market.get_or_register_trader(&WALLET.to_bytes());
+    market.get_or_register_trader(&trader.to_bytes());
     let pool_account = Account {
         lamports: litesvm.minimum_balance_for_rent_exemption(bytes.len()), //size might be change after insertion
         data: bytes.to_vec(),
@@ -134,7 +186,7 @@ pub fn add_seat_to_market(litesvm: &LiteSVM, rpc: &RpcClient, market: Pubkey) ->
 pub fn create_seat(litesvm: &LiteSVM, market: Pubkey, trader: Pubkey) -> Account {
     let discriminant = u64::from_le_bytes(
         keccak::hashv(&[
-            PHOENIX.as_ref(),
+            PHOENIX_PROGRAM_ID.as_ref(),
             "phoenix::program::accounts::Seat".as_bytes(),
         ])
         .as_ref()[..8]
@@ -151,7 +203,7 @@ pub fn create_seat(litesvm: &LiteSVM, market: Pubkey, trader: Pubkey) -> Account
     Account {
         lamports: litesvm.minimum_balance_for_rent_exemption(128),
         data,
-        owner: PHOENIX,
+        owner: PHOENIX_PROGRAM_ID,
         executable: false,
         rent_epoch: 0,
     }
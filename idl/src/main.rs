@@ -0,0 +1,222 @@
+/*
+Generates the phoenix_mm IDL (Anchor legacy / Shank JSON shape, which Codama can import).
+
+  cargo run -p idl              rewrite idl/phoenix_mm.json
+  cargo run -p idl -- --check   fail if idl/phoenix_mm.json is stale
+
+Instruction accounts and error codes are read from the program crate. Struct layouts are
+listed here by hand and checked against size_of, so a changed struct fails the generator
+instead of producing an IDL that decodes garbage.
+*/
+use phoenix_mm::error::PhoenixMmError;
+use phoenix_mm::instruction::{AccountSpec, INSTRUCTION_VERSION, PhoenixMmInstruction};
+use phoenix_mm::sdk::PROGRAM_ID;
+use phoenix_mm::types::{OrderParams, PhoenixStrategyState, StrategyParams};
+use serde_json::{Value, json};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy)]
+enum Ty {
+    U8,
+    U16,
+    U64,
+    I64,
+    PublicKey,
+    Defined(&'static str, usize),
+}
+impl Ty {
+    fn size(&self) -> usize {
+        match self {
+            Ty::U8 => 1,
+            Ty::U16 => 2,
+            Ty::U64 | Ty::I64 => 8,
+            Ty::PublicKey => 32,
+            Ty::Defined(_, size) => *size,
+        }
+    }
+
+    fn to_json(self) -> Value {
+        match self {
+            Ty::U8 => json!("u8"),
+            Ty::U16 => json!("u16"),
+            Ty::U64 => json!("u64"),
+            Ty::I64 => json!("i64"),
+            Ty::PublicKey => json!("publicKey"),
+            Ty::Defined(name, _) => json!({ "defined": name }),
+        }
+    }
+}
+
+const STRATEGY_PARAMS: &[(&str, Ty)] = &[
+    ("quoteEdgeInBps", Ty::U64),
+    ("quoteSizeInQuoteAtoms", Ty::U64),
+    ("priceImprovementBehavior", Ty::U8),
+    ("postOnly", Ty::U8),
+    ("coverTakerFee", Ty::U8),
+    ("hedgeCostInBps", Ty::U16),
+    ("requoteToleranceInTicks", Ty::U8),
+    ("keepRemainingInPct", Ty::U8),
+    ("minBestSizeInPct", Ty::U8),
+    ("minBestSizeInBaseLots", Ty::U64),
+    ("bestPriceDepthInBaseLots", Ty::U64),
+    ("pricingModel", Ty::U8),
+    ("riskAversionInMillionths", Ty::U64),
+    ("orderArrivalDecayInMillionths", Ty::U64),
+    ("volatilityInBps", Ty::U64),
+    ("volatilitySpanInSlots", Ty::U64),
+    ("volatilityEdgeMultiplierInHundredths", Ty::U16),
+];
+const ORDER_PARAMS: &[(&str, Ty)] = &[
+    ("fairPriceInQuoteAtomsPerRawBaseUnit", Ty::U64),
+    (
+        "strategyParams",
+        Ty::Defined("StrategyParams", size_of::<StrategyParams>()),
+    ),
+];
+const PHOENIX_STRATEGY_STATE: &[(&str, Ty)] = &[
+    ("trader", Ty::PublicKey),
+    ("market", Ty::PublicKey),
+    ("bidOrderSequenceNumber", Ty::U64),
+    ("bidPriceInTicks", Ty::U64),
+    ("initialBidSizeInBaseLots", Ty::U64),
+    ("askOrderSequenceNumber", Ty::U64),
+    ("askPriceInTicks", Ty::U64),
+    ("initialAskSizeInBaseLots", Ty::U64),
+    ("lastUpdateSlot", Ty::U64),
+    ("lastUpdateUnixTimestamp", Ty::I64),
+    ("quoteEdgeInBps", Ty::U64),
+    ("quoteSizeInQuoteAtoms", Ty::U64),
+    ("postOnly", Ty::U8),
+    ("priceImprovementBehavior", Ty::U8),
+    ("coverTakerFee", Ty::U8),
+    ("hedgeCostInBps", Ty::U16),
+    ("requoteToleranceInTicks", Ty::U8),
+    ("keepRemainingInPct", Ty::U8),
+    ("minBestSizeInPct", Ty::U8),
+    ("minBestSizeInBaseLots", Ty::U64),
+    ("bestPriceDepthInBaseLots", Ty::U64),
+    ("pricingModel", Ty::U8),
+    ("riskAversionInMillionths", Ty::U64),
+    ("orderArrivalDecayInMillionths", Ty::U64),
+    ("volatilityInBps", Ty::U64),
+    ("volatilitySpanInSlots", Ty::U64),
+    ("volatilityEdgeMultiplierInHundredths", Ty::U16),
+    ("lastFairPriceInQuoteAtomsPerRawBaseUnit", Ty::U64),
+    ("fairPriceVariance", Ty::U64),
+    ("version", Ty::U8),
+];
+
+fn struct_type(name: &str, fields: &[(&str, Ty)], size: usize) -> Value {
+    let layout_size: usize = fields.iter().map(|(_, ty)| ty.size()).sum();
+    assert_eq!(
+        layout_size, size,
+        "IDL layout of {} is out of date with the program",
+        name
+    );
+    json!({
+        "name": name,
+        "type": {
+            "kind": "struct",
+            "fields": fields
+                .iter()
+                .map(|(name, ty)| json!({ "name": name, "type": ty.to_json() }))
+                .collect::<Vec<_>>(),
+        },
+    })
+}
+
+fn camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn instruction(name: &str, tag: u8, accounts: &[AccountSpec], args: Option<&str>) -> Value {
+    // the version byte follows the tag, see instruction.rs
+    let mut arg_list = vec![json!({ "name": "version", "type": "u8" })];
+    arg_list.extend(args.map(|args| json!({ "name": "params", "type": { "defined": args } })));
+    json!({
+        "name": name,
+        "accounts": accounts
+            .iter()
+            .map(|account| json!({
+                "name": camel_case(account.name),
+                "isMut": account.writable,
+                "isSigner": account.signer,
+            }))
+            .collect::<Vec<_>>(),
+        "args": arg_list,
+        "discriminant": { "type": "u8", "value": tag },
+    })
+}
+
+fn idl() -> Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "name": "phoenix_mm",
+        "instructions": [
+            instruction(
+                "initialize",
+                PhoenixMmInstruction::INITIALIZE_TAG,
+                &PhoenixMmInstruction::INITIALIZE_ACCOUNTS,
+                Some("StrategyParams"),
+            ),
+            instruction(
+                "updateQuotes",
+                PhoenixMmInstruction::UPDATE_QUOTES_TAG,
+                &PhoenixMmInstruction::UPDATE_QUOTES_ACCOUNTS,
+                Some("OrderParams"),
+            ),
+            instruction(
+                "migrateStrategy",
+                PhoenixMmInstruction::MIGRATE_STRATEGY_TAG,
+                &PhoenixMmInstruction::MIGRATE_STRATEGY_ACCOUNTS,
+                None,
+            ),
+        ],
+        // PhoenixStrategyState is packed and has no discriminator, its version is the last field
+        "accounts": [struct_type(
+            "PhoenixStrategyState",
+            PHOENIX_STRATEGY_STATE,
+            size_of::<PhoenixStrategyState>(),
+        )],
+        "types": [
+            struct_type("StrategyParams", STRATEGY_PARAMS, size_of::<StrategyParams>()),
+            struct_type("OrderParams", ORDER_PARAMS, size_of::<OrderParams>()),
+        ],
+        "errors": PhoenixMmError::ALL
+            .iter()
+            .map(|e| json!({ "code": *e as u32, "name": e.name(), "msg": e.msg() }))
+            .collect::<Vec<_>>(),
+        "metadata": {
+            "origin": "shank",
+            "address": PROGRAM_ID.to_string(),
+            "instructionVersion": INSTRUCTION_VERSION,
+        },
+    })
+}
+
+fn main() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("phoenix_mm.json");
+    let generated = serde_json::to_string_pretty(&idl()).unwrap() + "\n";
+    if std::env::args().any(|arg| arg == "--check") {
+        let current = std::fs::read_to_string(&path).unwrap_or_default();
+        if current != generated {
+            eprintln!("{} is out of date, run `cargo run -p idl`", path.display());
+            std::process::exit(1);
+        }
+        return;
+    }
+    std::fs::write(&path, generated).unwrap();
+    println!("wrote {}", path.display());
+}
@@ -0,0 +1,318 @@
+/*
+Aggregated views of a FIFOMarket book.
+
+L2Book folds the resting orders of each side into price levels (price in ticks, total base
+lots), best level first, up to a fixed depth. It does not allocate, so the program can use it
+for pricing and the client for display. All helpers use integer math on ticks and base lots,
+rounding down.
+
+All scans skip orders that are expired at the given slot / unix timestamp: Phoenix only
+removes them lazily, they can not be filled anymore.
+
+l3_orders walks one side order by order, best first and in time priority within a level, and
+resolves each order's trader_index to the trader's pubkey.
+*/
+use crate::types::*;
+use arrayvec::ArrayVec;
+use pinocchio::pubkey::Pubkey;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct L2Level {
+    pub price_in_ticks: u64,
+    pub size_in_base_lots: u64,
+}
+
+/// Up to `DEPTH` price levels per side, best first
+pub struct L2Book<const DEPTH: usize> {
+    pub bids: ArrayVec<L2Level, DEPTH>,
+    pub asks: ArrayVec<L2Level, DEPTH>,
+}
+
+impl<const DEPTH: usize> L2Book<DEPTH> {
+    /// Aggregate the book, skipping the orders of `exclude_trader_index` (e.g. our own quotes)
+    pub fn from_market(
+        market: &dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+        exclude_trader_index: Option<u64>,
+        current_slot: u64,
+        current_unix_timestamp_in_seconds: u64,
+    ) -> Self {
+        let live_orders = |side| {
+            live_orders(
+                market,
+                side,
+                current_slot,
+                current_unix_timestamp_in_seconds,
+            )
+            .filter(|(_, order)| Some(order.trader_index) != exclude_trader_index)
+        };
+        L2Book {
+            bids: aggregate_levels(live_orders(Side::Bid))
+                .take(DEPTH)
+                .collect(),
+            asks: aggregate_levels(live_orders(Side::Ask))
+                .take(DEPTH)
+                .collect(),
+        }
+    }
+
+    pub fn levels(&self, side: Side) -> &[L2Level] {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<L2Level> {
+        self.bids.first().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<L2Level> {
+        self.asks.first().copied()
+    }
+
+    /// (best bid + best ask) / 2
+    pub fn mid_price_in_ticks(&self) -> Option<u64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        Some(((bid.price_in_ticks as u128 + ask.price_in_ticks as u128) / 2) as u64)
+    }
+
+    /// Top of book prices weighted by the opposite size: leans towards the side with less size.
+    /// The mid price when both best levels are empty
+    pub fn microprice_in_ticks(&self) -> Option<u64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        let total_size = bid.size_in_base_lots as u128 + ask.size_in_base_lots as u128;
+        if total_size == 0 {
+            return self.mid_price_in_ticks();
+        }
+        let weighted = bid.price_in_ticks as u128 * ask.size_in_base_lots as u128
+            + ask.price_in_ticks as u128 * bid.size_in_base_lots as u128;
+        Some((weighted / total_size) as u64)
+    }
+
+    /// (best ask - best bid) / mid, in bps
+    pub fn spread_in_bps(&self) -> Option<u64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        let mid = self.mid_price_in_ticks()?;
+        if mid == 0 {
+            return None;
+        }
+        let spread = ask.price_in_ticks.saturating_sub(bid.price_in_ticks);
+        Some((spread as u128 * 10_000 / mid as u128) as u64)
+    }
+
+    /// Base lots resting on `side` within `bps` of the mid price (levels beyond DEPTH are not counted)
+    pub fn depth_within_bps(&self, side: Side, bps: u64) -> u64 {
+        let Some(mid) = self.mid_price_in_ticks() else {
+            return 0;
+        };
+        let distance = (mid as u128 * bps as u128 / 10_000) as u64;
+        self.levels(side)
+            .iter()
+            .take_while(|level| match side {
+                Side::Bid => level.price_in_ticks >= mid.saturating_sub(distance),
+                Side::Ask => level.price_in_ticks <= mid.saturating_add(distance),
+            })
+            .map(|level| level.size_in_base_lots)
+            .sum()
+    }
+}
+
+/// One resting order with its trader resolved
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct L3Order {
+    pub side: Side,
+    pub order_id: FIFOOrderId,
+    pub size_in_base_lots: u64,
+    pub trader_index: u64,
+    /// None if the index does not resolve to a seat
+    pub trader: Option<Pubkey>,
+    pub last_valid_slot: Option<u64>,
+    pub last_valid_unix_timestamp_in_seconds: Option<u64>,
+}
+
+impl L3Order {
+    pub fn price_in_ticks(&self) -> u64 {
+        self.order_id.price_in_ticks.inner
+    }
+}
+
+/// Base lots resting before `order_id` at its price level (time priority)
+pub fn base_lots_ahead(
+    market: &dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    side: Side,
+    order_id: &FIFOOrderId,
+    current_slot: u64,
+    current_unix_timestamp_in_seconds: u64,
+) -> u64 {
+    live_orders(
+        market,
+        side,
+        current_slot,
+        current_unix_timestamp_in_seconds,
+    )
+    .take_while(|(id, _)| *id < order_id)
+    .filter(|(id, _)| id.price_in_ticks == order_id.price_in_ticks)
+    .map(|(_, order)| order.num_base_lots)
+    .sum()
+}
+
+/// Base lots resting at `price_in_ticks` on `side`, without the orders of `exclude_trader_index`
+pub fn base_lots_at_price(
+    market: &dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    side: Side,
+    price_in_ticks: u64,
+    exclude_trader_index: u64,
+    current_slot: u64,
+    current_unix_timestamp_in_seconds: u64,
+) -> u64 {
+    live_orders(
+        market,
+        side,
+        current_slot,
+        current_unix_timestamp_in_seconds,
+    )
+    .skip_while(|(id, _)| id.price_in_ticks.inner != price_in_ticks)
+    .take_while(|(id, _)| id.price_in_ticks.inner == price_in_ticks)
+    .filter(|(_, order)| order.trader_index != exclude_trader_index)
+    .map(|(_, order)| order.num_base_lots)
+    .sum()
+}
+
+/// Live resting orders of `side`, best price first
+pub fn l3_orders<'a>(
+    market: &'a dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    side: Side,
+    current_slot: u64,
+    current_unix_timestamp_in_seconds: u64,
+) -> impl Iterator<Item = L3Order> + 'a {
+    live_orders(
+        market,
+        side,
+        current_slot,
+        current_unix_timestamp_in_seconds,
+    )
+    .map(move |(order_id, order)| L3Order {
+        side,
+        order_id: *order_id,
+        size_in_base_lots: order.num_base_lots,
+        trader_index: order.trader_index,
+        // a free seat node still has a key, only a registered trader maps back to the index
+        trader: u32::try_from(order.trader_index).ok().and_then(|index| {
+            market
+                .get_trader_id_from_index(index)
+                .filter(|trader| market.get_trader_index(trader) == Some(index))
+        }),
+        last_valid_slot: order.last_valid_slot(),
+        last_valid_unix_timestamp_in_seconds: order.last_valid_unix_timestamp_in_seconds(),
+    })
+}
+
+/// Orders of `side` not expired at the given slot and timestamp, best price first
+pub fn live_orders<'a>(
+    market: &'a dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    side: Side,
+    current_slot: u64,
+    current_unix_timestamp_in_seconds: u64,
+) -> impl Iterator<Item = (&'a FIFOOrderId, &'a FIFORestingOrder)> + 'a {
+    market.get_book(side).iter().filter(move |(_, order)| {
+        !order.is_expired(current_slot, current_unix_timestamp_in_seconds)
+    })
+}
+
+/// Price levels of one side's `orders`, best first, aggregated lazily
+pub fn aggregate_levels<'a>(
+    orders: impl Iterator<Item = (&'a FIFOOrderId, &'a FIFORestingOrder)>,
+) -> impl Iterator<Item = L2Level> {
+    // FIFOOrderId orders both trees best price first, the orders of a level are adjacent
+    let mut orders = orders.peekable();
+    core::iter::from_fn(move || {
+        let (order_id, order) = orders.next()?;
+        let mut level = L2Level {
+            price_in_ticks: order_id.price_in_ticks.inner,
+            size_in_base_lots: order.num_base_lots,
+        };
+        while let Some((_, order)) =
+            orders.next_if(|(id, _)| id.price_in_ticks.inner == level.price_in_ticks)
+        {
+            level.size_in_base_lots += order.num_base_lots;
+        }
+        Some(level)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(bids: &[(u64, u64)], asks: &[(u64, u64)]) -> L2Book<4> {
+        let levels = |levels: &[(u64, u64)]| {
+            levels
+                .iter()
+                .map(|&(price_in_ticks, size_in_base_lots)| L2Level {
+                    price_in_ticks,
+                    size_in_base_lots,
+                })
+                .collect()
+        };
+        L2Book {
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    #[test]
+    fn top_of_book_prices_round_down() {
+        let l2 = book(&[(99, 10), (98, 5)], &[(102, 30), (103, 1)]);
+        // 100.5
+        assert_eq!(l2.mid_price_in_ticks(), Some(100));
+        // (99 * 30 + 102 * 10) / 40 = 99.75, leaning to the thinner bid
+        assert_eq!(l2.microprice_in_ticks(), Some(99));
+        // 3 / 100
+        assert_eq!(l2.spread_in_bps(), Some(300));
+        // 1 / 99 = 101.01 bps
+        assert_eq!(book(&[(99, 1)], &[(100, 1)]).spread_in_bps(), Some(101));
+    }
+
+    #[test]
+    fn microprice_without_size_is_the_mid() {
+        let l2 = book(&[(99, 0)], &[(102, 0)]);
+        assert_eq!(l2.microprice_in_ticks(), Some(100));
+        // one empty best level gives all the weight to its price
+        assert_eq!(
+            book(&[(99, 0)], &[(102, 7)]).microprice_in_ticks(),
+            Some(99)
+        );
+    }
+
+    #[test]
+    fn empty_side() {
+        for l2 in [
+            book(&[(99, 10)], &[]),
+            book(&[], &[(102, 30)]),
+            book(&[], &[]),
+        ] {
+            assert_eq!(l2.mid_price_in_ticks(), None);
+            assert_eq!(l2.microprice_in_ticks(), None);
+            assert_eq!(l2.spread_in_bps(), None);
+            assert_eq!(l2.depth_within_bps(Side::Bid, 10_000), 0);
+            assert_eq!(l2.depth_within_bps(Side::Ask, 10_000), 0);
+        }
+        // a zero mid has no relative spread
+        assert_eq!(book(&[(0, 1)], &[(1, 1)]).spread_in_bps(), None);
+    }
+
+    #[test]
+    fn depth_includes_the_level_on_the_boundary() {
+        // mid 1000, 100 bps is 10 ticks: 990 and 1010 are in, 989 and 1011 are out
+        let l2 = book(
+            &[(995, 1), (990, 2), (989, 4)],
+            &[(1005, 1), (1010, 2), (1011, 4)],
+        );
+        assert_eq!(l2.depth_within_bps(Side::Bid, 100), 3);
+        assert_eq!(l2.depth_within_bps(Side::Ask, 100), 3);
+        assert_eq!(l2.depth_within_bps(Side::Bid, 50), 1);
+        // 15 bps is 1.5 ticks, rounded down to 1: nothing within
+        assert_eq!(l2.depth_within_bps(Side::Ask, 15), 0);
+        assert_eq!(l2.depth_within_bps(Side::Bid, 0), 0);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::types::*;
+/*
+Unit conversions for a Phoenix market, all derived from its MarketHeader.
+
+  raw base unit  : one whole base token (1 SOL)
+  base unit      : raw_base_units_per_base_unit raw base units
+  base atom      : 10^-base_decimals raw base units (1 lamport)
+  base lot       : base_lot_size base atoms
+  quote atom     : 10^-quote_decimals quote tokens (1 micro USDC)
+  quote lot      : quote_lot_size quote atoms
+  tick           : tick_size_in_quote_atoms_per_base_unit quote atoms per base unit
+
+The integer conversions are used on-chain, the f64 ones are for the client and for display.
+*/
+
+fn pow10(decimals: u32) -> f64 {
+    10f64.powi(decimals as i32)
+}
+
+/// Fair price in quote atoms per raw base unit -> price in ticks (rounded down)
+pub fn quote_atoms_per_raw_base_unit_to_ticks(
+    header: &MarketHeader,
+    price_in_quote_atoms_per_raw_base_unit: u64,
+) -> u64 {
+    (price_in_quote_atoms_per_raw_base_unit as u128 * header.raw_base_units_per_base_unit as u128
+        / header.tick_size_in_quote_atoms_per_base_unit as u128) as u64
+}
+
+/// Price in ticks -> quote atoms per raw base unit (rounded down)
+pub fn ticks_to_quote_atoms_per_raw_base_unit(header: &MarketHeader, price_in_ticks: u64) -> u64 {
+    (price_in_ticks as u128 * header.tick_size_in_quote_atoms_per_base_unit as u128
+        / header.raw_base_units_per_base_unit as u128) as u64
+}
+
+/// UI price (e.g. 187.42 USDC per SOL) -> quote atoms per raw base unit, the unit of `OrderParams`
+pub fn ui_price_to_quote_atoms_per_raw_base_unit(header: &MarketHeader, price: f64) -> u64 {
+    (price * pow10(header.quote_params.decimals)).round() as u64
+}
+
+/// Quote atoms per raw base unit -> UI price
+pub fn quote_atoms_per_raw_base_unit_to_ui_price(
+    header: &MarketHeader,
+    price_in_quote_atoms_per_raw_base_unit: u64,
+) -> f64 {
+    price_in_quote_atoms_per_raw_base_unit as f64 / pow10(header.quote_params.decimals)
+}
+
+/// UI price -> price in ticks (rounded to the nearest tick)
+pub fn ui_price_to_ticks(header: &MarketHeader, price: f64) -> u64 {
+    (price * pow10(header.quote_params.decimals) * header.raw_base_units_per_base_unit as f64
+        / header.tick_size_in_quote_atoms_per_base_unit as f64)
+        .round() as u64
+}
+
+/// Price in ticks -> UI price
+pub fn ticks_to_ui_price(header: &MarketHeader, price_in_ticks: u64) -> f64 {
+    price_in_ticks as f64 * header.tick_size_in_quote_atoms_per_base_unit as f64
+        / header.raw_base_units_per_base_unit as f64
+        / pow10(header.quote_params.decimals)
+}
+
+/// Base lots -> UI size (e.g. SOL)
+pub fn base_lots_to_ui_size(header: &MarketHeader, base_lots: u64) -> f64 {
+    base_lots as f64 * header.base_lot_size as f64 / pow10(header.base_params.decimals)
+}
+
+/// UI size -> base lots (rounded down so we never quote more than requested)
+pub fn ui_size_to_base_lots(header: &MarketHeader, size: f64) -> u64 {
+    (size * pow10(header.base_params.decimals) / header.base_lot_size as f64).floor() as u64
+}
+
+/// Base atoms -> base lots (rounded down)
+pub fn base_atoms_to_base_lots(header: &MarketHeader, base_atoms: u64) -> u64 {
+    base_atoms / header.base_lot_size
+}
+
+/// Quote lots -> UI amount (e.g. USDC)
+pub fn quote_lots_to_ui_amount(header: &MarketHeader, quote_lots: u64) -> f64 {
+    quote_lots as f64 * header.quote_lot_size as f64 / pow10(header.quote_params.decimals)
+}
+
+/// Quote atoms -> UI amount (e.g. USDC)
+pub fn quote_atoms_to_ui_amount(header: &MarketHeader, quote_atoms: u64) -> f64 {
+    quote_atoms as f64 / pow10(header.quote_params.decimals)
+}
+
+/// UI amount -> quote atoms (rounded down)
+pub fn ui_amount_to_quote_atoms(header: &MarketHeader, amount: f64) -> u64 {
+    (amount * pow10(header.quote_params.decimals)).floor() as u64
+}
@@ -1,13 +1,13 @@
 #![allow(unexpected_cfgs)]
+use crate::book::*;
+use crate::conversions::*;
+use crate::error::PhoenixMmError;
+use crate::instruction::PhoenixMmInstruction;
+use crate::pricing::*;
 use crate::types::*;
 use crate::utils::*;
-use pinocchio_log::{
-    log,
-    logger::{Argument, Log, Logger},
-};
-use pinocchio_system::instructions::CreateAccount;
-use pinocchio_token::state::TokenAccount;
-//use crate::instruction::{self, MyProgramInstruction};
+use crate::volatility::*;
+use arrayvec::ArrayVec;
 use bytemuck::checked::try_from_bytes;
 use pinocchio::{
     ProgramResult,
@@ -20,6 +20,12 @@ use pinocchio::{
     pubkey::find_program_address,
     sysvars::{Sysvar, clock::Clock, rent::Rent},
 };
+use pinocchio_log::{
+    log,
+    logger::{Argument, Log, Logger},
+};
+use pinocchio_system::instructions::{CreateAccount, Transfer};
+use pinocchio_token::state::TokenAccount;
 
 // This is the entrypoint for the program.
 program_entrypoint!(process_instruction);
@@ -31,34 +37,32 @@ fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    let (ix_disc, instruction_data) = instruction_data
-        .split_first()
-        .ok_or(ProgramError::InvalidInstructionData)?;
-
-    match ix_disc {
-        0 => {
+    match PhoenixMmInstruction::unpack(instruction_data)? {
+        PhoenixMmInstruction::Initialize(params) => {
             log!("Initalize");
 
-            initialize(accounts, instruction_data)?;
+            initialize(accounts, &params)?;
             Ok(())
         }
-        1 => {
+        PhoenixMmInstruction::UpdateQuotes(params) => {
             log!("Update Quotes");
-            update_quotes(accounts, instruction_data)?;
+            update_quotes(accounts, &params)?;
 
             Ok(())
         }
-        _ => return Err(ProgramError::InvalidInstructionData),
+        PhoenixMmInstruction::MigrateStrategy => {
+            log!("Migrate Strategy");
+            migrate_strategy(accounts)
+        }
     }
 }
 /*
 create a strategy account that will save our bot config
 */
-pub fn initialize(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+pub fn initialize(accounts: &[AccountInfo], params: &StrategyParams) -> ProgramResult {
     let [phoenix_strategy_account, user, market, system_program] = accounts else {
         return Err(ProgramError::NotEnoughAccountKeys);
     };
-    let params = try_from_bytes::<StrategyParams>(&data).unwrap();
     let clock = Clock::get()?;
 
     let phoenix_strategy = PhoenixStrategyState {
@@ -76,7 +80,22 @@ pub fn initialize(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
         quote_size_in_quote_atoms: params.quote_size_in_quote_atoms,
         post_only: params.post_only,
         price_improvement_behavior: params.price_improvement_behavior,
-        padding: [0; 6],
+        cover_taker_fee: params.cover_taker_fee,
+        hedge_cost_in_bps: params.hedge_cost_in_bps,
+        requote_tolerance_in_ticks: params.requote_tolerance_in_ticks,
+        keep_remaining_in_pct: params.keep_remaining_in_pct,
+        min_best_size_in_pct: params.min_best_size_in_pct,
+        min_best_size_in_base_lots: params.min_best_size_in_base_lots,
+        best_price_depth_in_base_lots: params.best_price_depth_in_base_lots,
+        pricing_model: params.pricing_model,
+        risk_aversion_in_millionths: params.risk_aversion_in_millionths,
+        order_arrival_decay_in_millionths: params.order_arrival_decay_in_millionths,
+        volatility_in_bps: params.volatility_in_bps,
+        volatility_span_in_slots: params.volatility_span_in_slots,
+        volatility_edge_multiplier_in_hundredths: params.volatility_edge_multiplier_in_hundredths,
+        last_fair_price_in_quote_atoms_per_raw_base_unit: 0,
+        fair_price_variance: 0,
+        version: STRATEGY_STATE_VERSION,
     };
     //create phoniex strategy account
     let space = core::mem::size_of::<PhoenixStrategyState>();
@@ -113,7 +132,44 @@ pub fn initialize(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
     dst[..size_of::<PhoenixStrategyState>()].copy_from_slice(bytes);
     Ok(())
 }
-pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+/*
+grow a strategy account with a legacy layout to the current one, the trader pays the extra rent
+*/
+pub fn migrate_strategy(accounts: &[AccountInfo]) -> ProgramResult {
+    let [phoenix_strategy_account, user, system_program] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    check_strategy_address(phoenix_strategy_account, user)?;
+    let space = size_of::<PhoenixStrategyState>();
+    let data_len = phoenix_strategy_account.data_len();
+    if data_len == space {
+        // already migrated
+        return Ok(());
+    }
+    let phoenix_strategy =
+        PhoenixStrategyState::from_legacy_bytes(&phoenix_strategy_account.try_borrow_data()?)
+            .ok_or(ProgramError::InvalidAccountData)?;
+    if phoenix_strategy.trader != *user.key() {
+        return Err(PhoenixMmError::InvalidStrategyAccount.into());
+    }
+    let lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(phoenix_strategy_account.lamports());
+    if lamports > 0 {
+        Transfer {
+            from: user,
+            to: phoenix_strategy_account,
+            lamports,
+        }
+        .invoke()?;
+    }
+    phoenix_strategy_account.resize(space)?;
+    phoenix_strategy_account
+        .try_borrow_mut_data()?
+        .copy_from_slice(bytemuck::bytes_of(&phoenix_strategy));
+    Ok(())
+}
+pub fn update_quotes(accounts: &[AccountInfo], params: &OrderParams) -> ProgramResult {
     let mut logger = Logger::<100>::default();
     let [
         phoniex_strategy,
@@ -132,12 +188,10 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
         return Err(ProgramError::NotEnoughAccountKeys);
     };
     let clock = Clock::get()?;
-    //OrderParams
-    let params = try_from_bytes::<OrderParams>(&data).unwrap();
-
     //Strategy Account
-    let mut phoenix_strategy =
-        *try_from_bytes::<PhoenixStrategyState>(&phoniex_strategy.try_borrow_data()?).unwrap();
+    let mut phoenix_strategy = load_strategy_state(phoniex_strategy, user, pool)?;
+    // returns since the previous update feed the EWMA variance
+    let slots_since_last_update = clock.slot.saturating_sub(phoenix_strategy.last_update_slot);
     //track last update
     phoenix_strategy.last_update_slot = clock.slot;
     phoenix_strategy.last_update_unix_timestamp = clock.unix_timestamp;
@@ -149,31 +203,162 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
     phoenix_strategy.quote_size_in_quote_atoms = params.strategy_params.quote_size_in_quote_atoms; //order size
     phoenix_strategy.post_only = params.strategy_params.post_only;
     phoenix_strategy.price_improvement_behavior = params.strategy_params.price_improvement_behavior; //undercut competitors or stay passive.
+    phoenix_strategy.cover_taker_fee = params.strategy_params.cover_taker_fee;
+    phoenix_strategy.hedge_cost_in_bps = params.strategy_params.hedge_cost_in_bps;
+    phoenix_strategy.requote_tolerance_in_ticks = params.strategy_params.requote_tolerance_in_ticks;
+    phoenix_strategy.keep_remaining_in_pct = params.strategy_params.keep_remaining_in_pct;
+    phoenix_strategy.min_best_size_in_pct = params.strategy_params.min_best_size_in_pct;
+    phoenix_strategy.min_best_size_in_base_lots = params.strategy_params.min_best_size_in_base_lots;
+    phoenix_strategy.best_price_depth_in_base_lots =
+        params.strategy_params.best_price_depth_in_base_lots;
+    phoenix_strategy.pricing_model = params.strategy_params.pricing_model;
+    phoenix_strategy.risk_aversion_in_millionths =
+        params.strategy_params.risk_aversion_in_millionths;
+    phoenix_strategy.order_arrival_decay_in_millionths =
+        params.strategy_params.order_arrival_decay_in_millionths;
+    phoenix_strategy.volatility_in_bps = params.strategy_params.volatility_in_bps;
+    phoenix_strategy.volatility_span_in_slots = params.strategy_params.volatility_span_in_slots;
+    phoenix_strategy.volatility_edge_multiplier_in_hundredths = params
+        .strategy_params
+        .volatility_edge_multiplier_in_hundredths;
+    phoenix_strategy.fair_price_variance = update_variance(
+        phoenix_strategy.fair_price_variance,
+        phoenix_strategy.last_fair_price_in_quote_atoms_per_raw_base_unit,
+        params.fair_price_in_quote_atoms_per_raw_base_unit,
+        slots_since_last_update,
+        phoenix_strategy.volatility_span_in_slots,
+    );
+    phoenix_strategy.last_fair_price_in_quote_atoms_per_raw_base_unit =
+        params.fair_price_in_quote_atoms_per_raw_base_unit;
     let market_data = pool.try_borrow_data()?;
     let market_header = deserialize_market_header(&market_data[..size_of::<MarketHeader>()])?;
     let market = deserialize_market(&market_data, &market_header.market_size_params)?;
+    let market = &*market;
     // Compute quote prices
     //each phoniex market has a tick size (min inc allowed )
     // the price where the bot will buy
     // fair_price_in_ticks=(fair price *raw base units)/tick_size
     // edge_in_ticks = edge_in_bps * fair_price_in_ticks / 10_000;
+    // a fill we have to hedge as a taker costs the taker fee plus the hedge cost, quoting
+    // inside that loses money on every round trip
+    let min_edge_in_bps = if phoenix_strategy.cover_taker_fee == 1 {
+        get_min_edge_in_bps(
+            market.get_taker_fee_bps(),
+            phoenix_strategy.hedge_cost_in_bps,
+        )
+    } else {
+        0
+    };
+    // volatile periods widen the quotes on their own
+    let volatility_edge_in_bps = get_volatility_edge_in_bps(
+        phoenix_strategy.fair_price_variance,
+        phoenix_strategy.volatility_edge_multiplier_in_hundredths,
+    );
+    if volatility_edge_in_bps > 0 {
+        log!("Volatility Edge: {}", volatility_edge_in_bps);
+    }
+    let edge_in_bps = phoenix_strategy
+        .quote_edge_in_bps
+        .max(min_edge_in_bps)
+        .max(volatility_edge_in_bps);
     let mut bid_price_in_ticks = get_bid_price_in_ticks(
         params.fair_price_in_quote_atoms_per_raw_base_unit,
         &market_header,
-        phoenix_strategy.quote_edge_in_bps,
+        edge_in_bps,
     );
     //simiilar math
     //the price where the bot will sell
     let mut ask_price_in_ticks = get_ask_price_in_ticks(
         params.fair_price_in_quote_atoms_per_raw_base_unit,
         &market_header,
-        phoenix_strategy.quote_edge_in_bps,
+        edge_in_bps,
     );
     //Bid=fair_price*(1-edge_bps/10_000)
     //Ask=fair_price*(1+edge_bps/10_000)
     // Returns the best bid and ask prices that are not placed by the trader
     let trader_index = market.get_trader_index(user.key()).unwrap_or(u32::MAX) as u64;
-    let (best_bid, best_ask) = get_best_bid_and_ask(market, trader_index);
+    if let PricingModel::AvellanedaStoikov = PricingModel::from_u8(phoenix_strategy.pricing_model) {
+        // inventory: wallet balances plus the funds deposited on the market
+        let trader_state = market
+            .get_registered_traders()
+            .get(user.key())
+            .copied()
+            .unwrap_or_default();
+        let base_atoms = get_balance_in_atoms(
+            TokenAccount::from_account_info(base_account)?.amount(),
+            trader_state.base_lots_free.inner,
+            trader_state.base_lots_locked.inner,
+            market_header.base_lot_size,
+        )?;
+        let quote_atoms = get_balance_in_atoms(
+            TokenAccount::from_account_info(quote_account)?.amount(),
+            trader_state.quote_lots_free.inner,
+            trader_state.quote_lots_locked.inner,
+            market_header.quote_lot_size,
+        )?;
+        let inventory = get_inventory_in_quote_sizes(
+            &market_header,
+            base_atoms,
+            quote_atoms,
+            params.fair_price_in_quote_atoms_per_raw_base_unit,
+            phoenix_strategy.quote_size_in_quote_atoms,
+        );
+        let model = AvellanedaStoikov {
+            risk_aversion_in_millionths: phoenix_strategy.risk_aversion_in_millionths,
+            order_arrival_decay_in_millionths: phoenix_strategy.order_arrival_decay_in_millionths,
+            volatility_in_bps: phoenix_strategy.volatility_in_bps,
+        };
+        (bid_price_in_ticks, ask_price_in_ticks) = model.get_prices_in_ticks(
+            quote_atoms_per_raw_base_unit_to_ticks(
+                &market_header,
+                params.fair_price_in_quote_atoms_per_raw_base_unit,
+            ),
+            inventory,
+        );
+        log!("Inventory in quote sizes (1e-12): {}", inventory);
+        // the model's own sigma is configured, the measured one still widens its quotes
+        if volatility_edge_in_bps > 0 {
+            bid_price_in_ticks = bid_price_in_ticks.min(get_bid_price_in_ticks(
+                params.fair_price_in_quote_atoms_per_raw_base_unit,
+                &market_header,
+                volatility_edge_in_bps,
+            ));
+            ask_price_in_ticks = ask_price_in_ticks.max(get_ask_price_in_ticks(
+                params.fair_price_in_quote_atoms_per_raw_base_unit,
+                &market_header,
+                volatility_edge_in_bps,
+            ));
+        }
+    }
+    let current_slot = clock.slot;
+    let current_unix_timestamp = clock.unix_timestamp as u64;
+    // Compute quote amounts in base lots
+    let size_in_quote_lots =
+        phoenix_strategy.quote_size_in_quote_atoms / market_header.quote_lot_size;
+    // dust filter for the best bid/ask, relative to our size at the fair price
+    let fair_price_in_ticks = get_bid_price_in_ticks(
+        params.fair_price_in_quote_atoms_per_raw_base_unit,
+        &market_header,
+        0,
+    )
+    .max(1);
+    let fair_size_in_base_lots = size_in_quote_lots * market.get_base_lots_per_base_unit()
+        / (fair_price_in_ticks * market.get_tick_size());
+    let best_price_filter = BestPriceFilter {
+        min_level_size_in_base_lots: get_min_level_size_in_base_lots(
+            phoenix_strategy.min_best_size_in_base_lots,
+            phoenix_strategy.min_best_size_in_pct,
+            fair_size_in_base_lots,
+        ),
+        depth_in_base_lots: phoenix_strategy.best_price_depth_in_base_lots,
+    };
+    let (best_bid, best_ask) = get_best_bid_and_ask(
+        market,
+        trader_index,
+        current_slot,
+        current_unix_timestamp,
+        &best_price_filter,
+    );
     log!("Current Market");
     log!("Best Bid: {}", best_bid);
     log!("Best Ask: {}", best_ask);
@@ -197,13 +382,27 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
             // market prices
         }
     }
-    // Compute quote amounts in base lots
-    let size_in_quote_lots =
-        phoenix_strategy.quote_size_in_quote_atoms / market_header.quote_lot_size;
-
+    let (break_even_bid, break_even_ask) = if min_edge_in_bps > 0 {
+        get_break_even_prices_in_ticks(
+            params.fair_price_in_quote_atoms_per_raw_base_unit,
+            &market_header,
+            min_edge_in_bps,
+        )
+    } else {
+        (u64::MAX, 0)
+    };
+    if min_edge_in_bps > 0 {
+        (bid_price_in_ticks, ask_price_in_ticks) = clamp_to_break_even(
+            bid_price_in_ticks,
+            ask_price_in_ticks,
+            break_even_bid,
+            break_even_ask,
+        );
+        log!("Min Edge: {}", min_edge_in_bps);
+    }
     //size_in_base_lots=(quote_lots*base_lots+per_unit)/(price_in_ticks*tick_size);
     let bid_size_in_base_lots = size_in_quote_lots * market.get_base_lots_per_base_unit()
-        / (bid_price_in_ticks * market.get_tick_size());
+        / (bid_price_in_ticks.max(1) * market.get_tick_size());
     let ask_size_in_base_lots = size_in_quote_lots * market.get_base_lots_per_base_unit()
         / (ask_price_in_ticks * market.get_tick_size());
 
@@ -214,6 +413,10 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
     log!("ask_size_in_base_lots: {}", ask_size_in_base_lots);
     let mut update_bid = true; //wether new order is allowed or not 
     let mut update_ask = true;
+    let tolerance = RequoteTolerance {
+        in_ticks: phoenix_strategy.requote_tolerance_in_ticks as u64,
+        min_remaining_in_pct: phoenix_strategy.keep_remaining_in_pct as u64,
+    };
     //decide which existing orders shoulde be cancel or kept before posting new ones
     let orders_to_cancel = [
         (
@@ -238,6 +441,14 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
     .iter()
     .filter_map(|(side, price, order_id, initial_size)| {
         if let Some(resting_order) = market.get_book(*side).get(order_id) {
+            // Phoenix leaves expired orders on the book, cancelling frees their funds
+            if resting_order.is_expired(current_slot, current_unix_timestamp) {
+                log!(
+                    "Found expired resting order: {}",
+                    order_id.order_sequence_number
+                );
+                return Some(*order_id);
+            }
             // The order is 100% identical, do not cancel it
             if resting_order.num_base_lots == *initial_size
                 && order_id.price_in_ticks.inner == *price
@@ -253,6 +464,49 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
                 }
                 return None;
             }
+            // Close enough to the new quote: keep its queue priority unless that means
+            // quoting inside break-even
+            let resting = RestingQuote {
+                price_in_ticks: order_id.price_in_ticks.inner,
+                remaining_in_base_lots: resting_order.num_base_lots,
+                initial_in_base_lots: *initial_size,
+                base_lots_ahead: base_lots_ahead(
+                    market,
+                    *side,
+                    order_id,
+                    current_slot,
+                    current_unix_timestamp,
+                ),
+            };
+            let within_break_even = match side {
+                Side::Bid => resting.price_in_ticks <= break_even_bid,
+                Side::Ask => resting.price_in_ticks >= break_even_ask,
+            };
+            if within_break_even
+                && keep_resting_quote(
+                    &tolerance,
+                    &resting,
+                    *price,
+                    base_lots_at_price(
+                        market,
+                        *side,
+                        *price,
+                        trader_index,
+                        current_slot,
+                        current_unix_timestamp,
+                    ),
+                )
+            {
+                log!(
+                    "Keeping resting order for queue priority: {}",
+                    order_id.order_sequence_number
+                );
+                match side {
+                    Side::Bid => update_bid = false,
+                    Side::Ask => update_ask = false,
+                }
+                return None;
+            }
             // The order has been partially filled or reduced
             log!(
                 "Found Paritally filled resting order: {}",
@@ -267,7 +521,7 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
         // The order has been fully filled /cancelled ,or the traders dosnet have any orders on the markt
         None
     })
-    .collect::<Vec<FIFOOrderId>>();
+    .collect::<ArrayVec<FIFOOrderId, MAX_CANCEL_ORDERS>>();
 
     // Drop reference prior to invoking
     drop(market_data);
@@ -283,7 +537,7 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
                     price_in_ticks: o_id.price_in_ticks.inner,
                     side: Side::from_order_sequence_number(o_id.order_sequence_number),
                 })
-                .collect::<Vec<_>>(),
+                .collect(),
         };
         create_cancel_multiple_orders_by_id_with_free_funds_instruction(
             phoenix_program,
@@ -300,29 +554,29 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
 
     if !update_ask && !update_bid && orders_to_cancel.is_empty() {
         log!("No orders to update");
-        return Ok(());
+        return store_strategy_state(phoniex_strategy, &phoenix_strategy);
     }
-    let mut order_ids: Vec<FIFOOrderId> = vec![];
+    let mut order_ids = ArrayVec::<FIFOOrderId, MAX_RETURNED_ORDER_IDS>::new();
     if phoenix_strategy.post_only == 1
         || !matches!(price_improvement_behavior, PriceImprovementBehavior::Join)
     {
         // Send multiple post-only orders in a single instruction
         let multiple_order_packet = MultipleOrderPacket::new(
             if update_bid {
-                vec![CondensedOrder::new_default(
+                ArrayVec::from([CondensedOrder::new_default(
                     bid_price_in_ticks,
                     bid_size_in_base_lots,
-                )]
+                )])
             } else {
-                vec![]
+                ArrayVec::new()
             },
             if update_ask {
-                vec![CondensedOrder::new_default(
+                ArrayVec::from([CondensedOrder::new_default(
                     ask_price_in_ticks,
                     ask_size_in_base_lots,
-                )]
+                )])
             } else {
-                vec![]
+                ArrayVec::new()
             },
             Some(client_order_id),
             false,
@@ -426,5 +680,73 @@ pub fn update_quotes(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
             .amount()
     );
 
+    store_strategy_state(phoniex_strategy, &phoenix_strategy)
+}
+
+// wallet balance plus the lots deposited on the market, in atoms
+fn get_balance_in_atoms(
+    wallet_atoms: u64,
+    lots_free: u64,
+    lots_locked: u64,
+    lot_size: u64,
+) -> Result<u64, ProgramError> {
+    lots_free
+        .checked_add(lots_locked)
+        .and_then(|lots| lots.checked_mul(lot_size))
+        .and_then(|atoms| atoms.checked_add(wallet_atoms))
+        .ok_or(ProgramError::ArithmeticOverflow)
+}
+
+// The state is written back at the end of update_quotes, only the trader's own strategy PDA
+// for this market may be passed in
+fn load_strategy_state(
+    phoenix_strategy_account: &AccountInfo,
+    user: &AccountInfo,
+    market: &AccountInfo,
+) -> Result<PhoenixStrategyState, ProgramError> {
+    check_strategy_address(phoenix_strategy_account, user)?;
+    let data = phoenix_strategy_account.try_borrow_data()?;
+    if data.len() != size_of::<PhoenixStrategyState>() {
+        return Err(if LEGACY_STRATEGY_STATE_SIZES.contains(&data.len()) {
+            PhoenixMmError::OutdatedStrategyAccount.into()
+        } else {
+            ProgramError::InvalidAccountData
+        });
+    }
+    let phoenix_strategy = *try_from_bytes::<PhoenixStrategyState>(&data)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if phoenix_strategy.version != STRATEGY_STATE_VERSION {
+        return Err(PhoenixMmError::OutdatedStrategyAccount.into());
+    }
+    if phoenix_strategy.trader != *user.key() || phoenix_strategy.market != *market.key() {
+        return Err(PhoenixMmError::InvalidStrategyAccount.into());
+    }
+    Ok(phoenix_strategy)
+}
+
+// signed by the trader, and the trader's strategy PDA owned by this program
+fn check_strategy_address(
+    phoenix_strategy_account: &AccountInfo,
+    user: &AccountInfo,
+) -> ProgramResult {
+    if !user.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !phoenix_strategy_account.is_owned_by(&crate::ID) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    let seeds: [&[u8]; 2] = [b"phoenix_strategy".as_ref(), user.key().as_ref()];
+    if find_program_address(&seeds, &crate::ID).0 != *phoenix_strategy_account.key() {
+        return Err(PhoenixMmError::InvalidStrategyAccount.into());
+    }
+    Ok(())
+}
+
+fn store_strategy_state(
+    phoenix_strategy_account: &AccountInfo,
+    phoenix_strategy: &PhoenixStrategyState,
+) -> ProgramResult {
+    let mut data = phoenix_strategy_account.try_borrow_mut_data()?;
+    data[..size_of::<PhoenixStrategyState>()].copy_from_slice(bytemuck::bytes_of(phoenix_strategy));
     Ok(())
 }
@@ -0,0 +1,71 @@
+use pinocchio::program_error::ProgramError;
+
+/// Custom errors of the phoenix_mm program, returned as `ProgramError::Custom(code)`
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhoenixMmError {
+    /// Instruction data is empty or the tag is unknown
+    InvalidInstruction = 0,
+    /// The wire format version byte is not supported by this build
+    UnsupportedInstructionVersion = 1,
+    /// The payload length does not match the instruction layout
+    InvalidInstructionLength = 2,
+    /// A strategy parameter is out of range
+    InvalidStrategyParams = 3,
+    /// The strategy account is not the trader's strategy PDA for this market
+    InvalidStrategyAccount = 4,
+    /// The strategy account has an older layout, send `MigrateStrategy` first
+    OutdatedStrategyAccount = 5,
+    /// Phoenix returned more order ids than fit in `MAX_RETURNED_ORDER_IDS`
+    TooManyReturnedOrderIds = 6,
+}
+impl PhoenixMmError {
+    pub const ALL: [PhoenixMmError; 7] = [
+        PhoenixMmError::InvalidInstruction,
+        PhoenixMmError::UnsupportedInstructionVersion,
+        PhoenixMmError::InvalidInstructionLength,
+        PhoenixMmError::InvalidStrategyParams,
+        PhoenixMmError::InvalidStrategyAccount,
+        PhoenixMmError::OutdatedStrategyAccount,
+        PhoenixMmError::TooManyReturnedOrderIds,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PhoenixMmError::InvalidInstruction => "InvalidInstruction",
+            PhoenixMmError::UnsupportedInstructionVersion => "UnsupportedInstructionVersion",
+            PhoenixMmError::InvalidInstructionLength => "InvalidInstructionLength",
+            PhoenixMmError::InvalidStrategyParams => "InvalidStrategyParams",
+            PhoenixMmError::InvalidStrategyAccount => "InvalidStrategyAccount",
+            PhoenixMmError::OutdatedStrategyAccount => "OutdatedStrategyAccount",
+            PhoenixMmError::TooManyReturnedOrderIds => "TooManyReturnedOrderIds",
+        }
+    }
+
+    pub fn msg(&self) -> &'static str {
+        match self {
+            PhoenixMmError::InvalidInstruction => "Instruction data is empty or the tag is unknown",
+            PhoenixMmError::UnsupportedInstructionVersion => {
+                "Instruction wire format version is not supported"
+            }
+            PhoenixMmError::InvalidInstructionLength => {
+                "Instruction payload length does not match its layout"
+            }
+            PhoenixMmError::InvalidStrategyParams => "A strategy parameter is out of range",
+            PhoenixMmError::InvalidStrategyAccount => {
+                "Strategy account does not belong to this trader and market"
+            }
+            PhoenixMmError::OutdatedStrategyAccount => {
+                "Strategy account layout is outdated, migrate it first"
+            }
+            PhoenixMmError::TooManyReturnedOrderIds => {
+                "Phoenix returned more order ids than the program can track"
+            }
+        }
+    }
+}
+impl From<PhoenixMmError> for ProgramError {
+    fn from(e: PhoenixMmError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
@@ -0,0 +1,155 @@
+use crate::error::PhoenixMmError;
+use crate::pricing::*;
+use crate::types::*;
+use arrayvec::ArrayVec;
+/*
+Wire format of every phoenix_mm instruction:
+
+  byte 0     tag      0 = Initialize, 1 = UpdateQuotes, 2 = MigrateStrategy
+  byte 1     version  INSTRUCTION_VERSION, bumped whenever a payload layout changes
+  bytes 2..  payload  packed little endian Pod struct, exact length required
+               Initialize      -> StrategyParams
+               UpdateQuotes    -> OrderParams
+               MigrateStrategy -> empty
+
+The same decoder runs in the program, the sdk builders and any off-chain indexer.
+*/
+
+pub const INSTRUCTION_VERSION: u8 = 4;
+const HEADER_LEN: usize = 2;
+/// Longest encoded instruction
+pub const MAX_INSTRUCTION_LEN: usize = HEADER_LEN + size_of::<OrderParams>();
+
+/// Name and flags of an instruction account, in instruction order
+pub struct AccountSpec {
+    pub name: &'static str,
+    pub writable: bool,
+    pub signer: bool,
+}
+const fn account(name: &'static str, writable: bool, signer: bool) -> AccountSpec {
+    AccountSpec {
+        name,
+        writable,
+        signer,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum PhoenixMmInstruction {
+    /// Create the strategy account of the trader, accounts: `INITIALIZE_ACCOUNTS`
+    Initialize(StrategyParams),
+    /// Cancel/replace the trader's quotes around a fair price, accounts: `UPDATE_QUOTES_ACCOUNTS`
+    UpdateQuotes(OrderParams),
+    /// Grow a strategy account with a legacy layout to the current one,
+    /// accounts: `MIGRATE_STRATEGY_ACCOUNTS`
+    MigrateStrategy,
+}
+impl PhoenixMmInstruction {
+    pub const INITIALIZE_TAG: u8 = 0;
+    pub const UPDATE_QUOTES_TAG: u8 = 1;
+    pub const MIGRATE_STRATEGY_TAG: u8 = 2;
+
+    pub const INITIALIZE_ACCOUNTS: [AccountSpec; 4] = [
+        account("phoenix_strategy", true, false),
+        account("trader", true, true),
+        account("market", false, false),
+        account("system_program", false, false),
+    ];
+    pub const UPDATE_QUOTES_ACCOUNTS: [AccountSpec; 11] = [
+        account("phoenix_strategy", true, false),
+        account("market", true, false),
+        account("trader", true, true),
+        account("phoenix_program", false, false),
+        account("phoenix_log_authority", false, false),
+        account("seat", true, false),
+        account("base_account", true, false),
+        account("quote_account", true, false),
+        account("base_vault", true, false),
+        account("quote_vault", true, false),
+        account("token_program", false, false),
+    ];
+    pub const MIGRATE_STRATEGY_ACCOUNTS: [AccountSpec; 3] = [
+        account("phoenix_strategy", true, false),
+        account("trader", true, true),
+        account("system_program", false, false),
+    ];
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            PhoenixMmInstruction::Initialize(_) => Self::INITIALIZE_TAG,
+            PhoenixMmInstruction::UpdateQuotes(_) => Self::UPDATE_QUOTES_TAG,
+            PhoenixMmInstruction::MigrateStrategy => Self::MIGRATE_STRATEGY_TAG,
+        }
+    }
+
+    pub fn unpack(data: &[u8]) -> Result<Self, PhoenixMmError> {
+        let (&tag, rest) = data
+            .split_first()
+            .ok_or(PhoenixMmError::InvalidInstruction)?;
+        let (&version, payload) = rest
+            .split_first()
+            .ok_or(PhoenixMmError::InvalidInstructionLength)?;
+        if version != INSTRUCTION_VERSION {
+            return Err(PhoenixMmError::UnsupportedInstructionVersion);
+        }
+        let instruction = match tag {
+            Self::INITIALIZE_TAG => PhoenixMmInstruction::Initialize(read_payload(payload)?),
+            Self::UPDATE_QUOTES_TAG => PhoenixMmInstruction::UpdateQuotes(read_payload(payload)?),
+            Self::MIGRATE_STRATEGY_TAG if payload.is_empty() => {
+                PhoenixMmInstruction::MigrateStrategy
+            }
+            Self::MIGRATE_STRATEGY_TAG => return Err(PhoenixMmError::InvalidInstructionLength),
+            _ => return Err(PhoenixMmError::InvalidInstruction),
+        };
+        match &instruction {
+            PhoenixMmInstruction::Initialize(params) => params.validate()?,
+            PhoenixMmInstruction::UpdateQuotes(params) => params.strategy_params.validate()?,
+            PhoenixMmInstruction::MigrateStrategy => {}
+        }
+        Ok(instruction)
+    }
+
+    pub fn pack(&self) -> ArrayVec<u8, MAX_INSTRUCTION_LEN> {
+        let mut data = ArrayVec::new();
+        data.push(self.tag());
+        data.push(INSTRUCTION_VERSION);
+        let payload = match self {
+            PhoenixMmInstruction::Initialize(params) => bytemuck::bytes_of(params),
+            PhoenixMmInstruction::UpdateQuotes(params) => bytemuck::bytes_of(params),
+            PhoenixMmInstruction::MigrateStrategy => &[],
+        };
+        data.try_extend_from_slice(payload).unwrap();
+        data
+    }
+}
+
+fn read_payload<T: bytemuck::Pod>(payload: &[u8]) -> Result<T, PhoenixMmError> {
+    bytemuck::try_pod_read_unaligned(payload).map_err(|_| PhoenixMmError::InvalidInstructionLength)
+}
+
+impl StrategyParams {
+    pub fn validate(&self) -> Result<(), PhoenixMmError> {
+        let hedge_cost_in_bps = self.hedge_cost_in_bps;
+        let risk_aversion_in_millionths = self.risk_aversion_in_millionths;
+        let order_arrival_decay_in_millionths = self.order_arrival_decay_in_millionths;
+        let volatility_in_bps = self.volatility_in_bps;
+        if self.price_improvement_behavior > PriceImprovementBehavior::Ignore.to_u8()
+            || self.post_only > 1
+            || self.cover_taker_fee > 1
+            || hedge_cost_in_bps >= 10_000
+            || self.keep_remaining_in_pct > 100
+            || self.pricing_model > PricingModel::AvellanedaStoikov.to_u8()
+            || risk_aversion_in_millionths > MAX_RISK_AVERSION_IN_MILLIONTHS
+            || volatility_in_bps > MAX_VOLATILITY_IN_BPS
+        {
+            return Err(PhoenixMmError::InvalidStrategyParams);
+        }
+        // kappa divides the arrival term
+        if self.pricing_model == PricingModel::AvellanedaStoikov.to_u8()
+            && order_arrival_decay_in_millionths == 0
+        {
+            return Err(PhoenixMmError::InvalidStrategyParams);
+        }
+        Ok(())
+    }
+}
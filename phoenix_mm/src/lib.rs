@@ -1,7 +1,16 @@
 #![allow(warnings)]
+pub mod book;
+pub mod conversions;
 #[cfg(not(feature = "no-entrypoint"))]
 mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod market_view;
+pub mod pricing;
+#[cfg(feature = "client")]
+pub mod sdk;
 pub mod types;
 pub mod utils;
+pub mod volatility;
 
 pinocchio_pubkey::declare_id!("6RavfKEf7qqJLXmmwUWVBkaN56pZ71JtqCFfS99bHrpu");
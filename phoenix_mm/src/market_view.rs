@@ -0,0 +1,429 @@
+use crate::types::*;
+use arrayvec::ArrayVec;
+use bytemuck::{Pod, Zeroable};
+use core::cmp::Ordering;
+use core::marker::PhantomData;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use sokoban::SENTINEL;
+use sokoban::node_allocator::{Node, NodeAllocatorMap, OrderedNodeAllocatorMap, TreeField};
+use sokoban::red_black_tree::RBNode;
+
+// sokoban RedBlackTree layout: root (u32) + padding, then the NodeAllocator
+// (size u64, bump_index u32, free_list_head u32) followed by the nodes
+const TREE_SIZE_OFFSET: usize = 16;
+const TREE_NODES_OFFSET: usize = 32;
+// registers (left, right, parent, color) precede the key/value of each node
+const NODE_REGISTERS_LEN: usize = 16;
+// a red-black tree of n nodes is at most 2 * log2(n + 1) deep
+const MAX_TREE_DEPTH: usize = 64;
+
+/// FIFOMarket fields stored before the trees
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct FIFOMarketFields {
+    _padding: [u64; 32],
+    base_lots_per_base_unit: u64,
+    tick_size_in_quote_lots_per_base_unit: u64,
+    order_sequence_number: u64,
+    taker_fee_bps: u64,
+    collected_quote_lot_fees: QuoteLots,
+    unclaimed_quote_lot_fees: QuoteLots,
+}
+
+/// Read only view of a sokoban RedBlackTree whose capacity is only known at runtime.
+/// Node indices are bounds checked, a malformed tree ends iteration and misses lookups
+/// instead of panicking.
+pub struct RedBlackTreeView<'a, K, V> {
+    data: &'a [u8],
+    capacity: usize,
+    _marker: PhantomData<(K, V)>,
+}
+
+impl<'a, K: Pod + Ord + Default, V: Pod + Default> RedBlackTreeView<'a, K, V> {
+    const NODE_SIZE: usize = size_of::<Node<RBNode<K, V>, 4>>();
+
+    /// Size in bytes of a tree holding `capacity` nodes
+    pub const fn data_size(capacity: usize) -> usize {
+        TREE_NODES_OFFSET + capacity * Self::NODE_SIZE
+    }
+
+    // `data` holds at least `data_size(capacity)` bytes, checked by FIFOMarketView::load
+    fn new(data: &'a [u8], capacity: usize) -> Result<Self, ProgramError> {
+        let tree = RedBlackTreeView {
+            data,
+            capacity,
+            _marker: PhantomData,
+        };
+        if tree.len() > capacity || tree.root() as usize > capacity {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(tree)
+    }
+
+    fn read_u32(&self, offset: usize) -> Result<u32, ProgramError> {
+        self.data
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+
+    fn node_offset(&self, index: u32) -> Result<usize, ProgramError> {
+        if index == SENTINEL || index as usize > self.capacity {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(TREE_NODES_OFFSET + (index as usize - 1) * Self::NODE_SIZE)
+    }
+
+    fn register(&self, index: u32, field: TreeField) -> Result<u32, ProgramError> {
+        let next = self.read_u32(self.node_offset(index)? + 4 * field as usize)?;
+        if next as usize > self.capacity {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(next)
+    }
+
+    fn node(&self, index: u32) -> Result<&'a RBNode<K, V>, ProgramError> {
+        let start = self.node_offset(index)? + NODE_REGISTERS_LEN;
+        self.data
+            .get(start..start + size_of::<RBNode<K, V>>())
+            .map(bytemuck::from_bytes)
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+
+    fn root(&self) -> u32 {
+        self.read_u32(0).unwrap_or(SENTINEL)
+    }
+
+    pub fn len(&self) -> usize {
+        u64::from_le_bytes(
+            self.data[TREE_SIZE_OFFSET..TREE_SIZE_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        ) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Node index of `key`, SENTINEL when absent. Errors on a malformed tree
+    pub fn try_get_addr(&self, key: &K) -> Result<u32, ProgramError> {
+        let mut index = self.root();
+        for _ in 0..MAX_TREE_DEPTH {
+            if index == SENTINEL {
+                return Ok(SENTINEL);
+            }
+            index = match key.cmp(&self.node(index)?.key) {
+                Ordering::Less => self.register(index, TreeField::Left)?,
+                Ordering::Greater => self.register(index, TreeField::Right)?,
+                Ordering::Equal => return Ok(index),
+            };
+        }
+        Err(ProgramError::InvalidAccountData)
+    }
+
+    /// Node index of `key`, SENTINEL when absent or the tree is malformed
+    pub fn get_addr(&self, key: &K) -> u32 {
+        self.try_get_addr(key).unwrap_or(SENTINEL)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&'a V> {
+        match self.get_addr(key) {
+            SENTINEL => None,
+            index => self.node(index).ok().map(|node| &node.value),
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.get_addr(key) != SENTINEL
+    }
+
+    pub fn get_min(&self) -> Option<(&'a K, &'a V)> {
+        self.iter().next()
+    }
+
+    pub fn get_max(&self) -> Option<(&'a K, &'a V)> {
+        self.iter().next_back()
+    }
+
+    /// In-order iteration, without allocating
+    pub fn iter(&self) -> RedBlackTreeViewIter<'a, K, V> {
+        RedBlackTreeViewIter {
+            tree: RedBlackTreeView {
+                data: self.data,
+                capacity: self.capacity,
+                _marker: PhantomData,
+            },
+            fwd_stack: ArrayVec::new(),
+            fwd_ptr: self.root(),
+            fwd_node: None,
+            rev_stack: ArrayVec::new(),
+            rev_ptr: self.root(),
+            rev_node: None,
+            terminated: false,
+        }
+    }
+
+    // index of the leftmost (Left) or rightmost (Right) node, SENTINEL when empty or malformed
+    fn extreme_index(&self, field: TreeField) -> u32 {
+        let mut index = self.root();
+        for _ in 0..MAX_TREE_DEPTH {
+            match self.register(index, field) {
+                Ok(SENTINEL) => return index,
+                Ok(next) => index = next,
+                Err(_) => return SENTINEL,
+            }
+        }
+        SENTINEL
+    }
+}
+
+// Read only: writes fail the way they do on a full tree
+impl<'a, K: Pod + Ord + Default, V: Pod + Default> NodeAllocatorMap<K, V>
+    for RedBlackTreeView<'a, K, V>
+{
+    fn insert(&mut self, _key: K, _value: V) -> Option<u32> {
+        None
+    }
+    fn remove(&mut self, _key: &K) -> Option<V> {
+        None
+    }
+    fn contains(&self, key: &K) -> bool {
+        RedBlackTreeView::contains(self, key)
+    }
+    fn get(&self, key: &K) -> Option<&V> {
+        RedBlackTreeView::get(self, key)
+    }
+    fn get_mut(&mut self, _key: &K) -> Option<&mut V> {
+        None
+    }
+    fn size(&self) -> usize {
+        RedBlackTreeView::len(self)
+    }
+    fn len(&self) -> usize {
+        RedBlackTreeView::len(self)
+    }
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+    fn iter(&self) -> Box<dyn DoubleEndedIterator<Item = (&K, &V)> + '_> {
+        Box::new(RedBlackTreeView::iter(self))
+    }
+    fn iter_mut(&mut self) -> Box<dyn DoubleEndedIterator<Item = (&K, &mut V)> + '_> {
+        Box::new(core::iter::empty())
+    }
+}
+
+impl<'a, K: Pod + Ord + Default, V: Pod + Default> OrderedNodeAllocatorMap<K, V>
+    for RedBlackTreeView<'a, K, V>
+{
+    fn get_min_index(&mut self) -> u32 {
+        self.extreme_index(TreeField::Left)
+    }
+    fn get_max_index(&mut self) -> u32 {
+        self.extreme_index(TreeField::Right)
+    }
+    fn get_min(&mut self) -> Option<(K, V)> {
+        RedBlackTreeView::get_min(self).map(|(key, value)| (*key, *value))
+    }
+    fn get_max(&mut self) -> Option<(K, V)> {
+        RedBlackTreeView::get_max(self).map(|(key, value)| (*key, *value))
+    }
+}
+
+/// Same traversal as sokoban's RedBlackTreeIterator, with fixed-capacity stacks.
+/// Iteration stops early on a malformed tree.
+pub struct RedBlackTreeViewIter<'a, K, V> {
+    tree: RedBlackTreeView<'a, K, V>,
+    fwd_stack: ArrayVec<u32, MAX_TREE_DEPTH>,
+    fwd_ptr: u32,
+    fwd_node: Option<u32>,
+    rev_stack: ArrayVec<u32, MAX_TREE_DEPTH>,
+    rev_ptr: u32,
+    rev_node: Option<u32>,
+    terminated: bool,
+}
+
+impl<'a, K: Pod + Ord + Default, V: Pod + Default> RedBlackTreeViewIter<'a, K, V> {
+    fn step(&mut self, forward: bool) -> Result<Option<(&'a K, &'a V)>, ProgramError> {
+        let (stack, ptr, node, other_node, down, up) = if forward {
+            (
+                &mut self.fwd_stack,
+                &mut self.fwd_ptr,
+                &mut self.fwd_node,
+                self.rev_node,
+                TreeField::Left,
+                TreeField::Right,
+            )
+        } else {
+            (
+                &mut self.rev_stack,
+                &mut self.rev_ptr,
+                &mut self.rev_node,
+                self.fwd_node,
+                TreeField::Right,
+                TreeField::Left,
+            )
+        };
+        while !self.terminated && (!stack.is_empty() || *ptr != SENTINEL) {
+            if *ptr != SENTINEL {
+                stack
+                    .try_push(*ptr)
+                    .map_err(|_| ProgramError::InvalidAccountData)?;
+                *ptr = self.tree.register(*ptr, down)?;
+            } else {
+                let current = stack.pop();
+                if current == other_node {
+                    self.terminated = true;
+                    return Ok(None);
+                }
+                *node = current;
+                let index = current.ok_or(ProgramError::InvalidAccountData)?;
+                *ptr = self.tree.register(index, up)?;
+                let entry = self.tree.node(index)?;
+                return Ok(Some((&entry.key, &entry.value)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a, K: Pod + Ord + Default, V: Pod + Default> Iterator for RedBlackTreeViewIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(true).unwrap_or_else(|_| {
+            self.terminated = true;
+            None
+        })
+    }
+}
+
+impl<'a, K: Pod + Ord + Default, V: Pod + Default> DoubleEndedIterator
+    for RedBlackTreeViewIter<'a, K, V>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(false).unwrap_or_else(|_| {
+            self.terminated = true;
+            None
+        })
+    }
+}
+
+/// Read only FIFOMarket of any size: tree offsets come from MarketSizeParams instead of
+/// const generics, so markets outside SUPPORTED_MARKET_SIZES can be read too
+pub struct FIFOMarketView<'a> {
+    fields: &'a FIFOMarketFields,
+    data_size: usize,
+    bids: RedBlackTreeView<'a, FIFOOrderId, FIFORestingOrder>,
+    asks: RedBlackTreeView<'a, FIFOOrderId, FIFORestingOrder>,
+    traders: RedBlackTreeView<'a, Pubkey, TraderState>,
+}
+
+impl<'a> FIFOMarketView<'a> {
+    /// Size of the market body (header excluded)
+    pub fn data_size(market_size_params: &MarketSizeParams) -> usize {
+        size_of::<FIFOMarketFields>()
+            + RedBlackTreeView::<FIFOOrderId, FIFORestingOrder>::data_size(
+                market_size_params.bids_size as usize,
+            )
+            + RedBlackTreeView::<FIFOOrderId, FIFORestingOrder>::data_size(
+                market_size_params.asks_size as usize,
+            )
+            + RedBlackTreeView::<Pubkey, TraderState>::data_size(
+                market_size_params.num_seats as usize,
+            )
+    }
+
+    /// `market_bytes` is the whole market account, header included
+    pub fn load(
+        market_bytes: &'a [u8],
+        market_size_params: &MarketSizeParams,
+    ) -> Result<Self, ProgramError> {
+        let body = market_bytes
+            .get(size_of::<MarketHeader>()..)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let data_size = Self::data_size(market_size_params);
+        if body.len() < data_size || body.as_ptr().align_offset(align_of::<FIFOMarketFields>()) != 0
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (fields, trees) = body.split_at(size_of::<FIFOMarketFields>());
+        let (bids, trees) = trees.split_at(
+            RedBlackTreeView::<FIFOOrderId, FIFORestingOrder>::data_size(
+                market_size_params.bids_size as usize,
+            ),
+        );
+        let (asks, traders) = trees.split_at(
+            RedBlackTreeView::<FIFOOrderId, FIFORestingOrder>::data_size(
+                market_size_params.asks_size as usize,
+            ),
+        );
+        Ok(FIFOMarketView {
+            fields: bytemuck::from_bytes(fields),
+            data_size,
+            bids: RedBlackTreeView::new(bids, market_size_params.bids_size as usize)?,
+            asks: RedBlackTreeView::new(asks, market_size_params.asks_size as usize)?,
+            traders: RedBlackTreeView::new(traders, market_size_params.num_seats as usize)?,
+        })
+    }
+
+    /// Book of `side` as a concrete tree, iterating it does not allocate
+    pub fn get_book_view(
+        &self,
+        side: Side,
+    ) -> &RedBlackTreeView<'a, FIFOOrderId, FIFORestingOrder> {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    /// Registered traders as a concrete tree, iterating it does not allocate
+    pub fn get_registered_traders_view(&self) -> &RedBlackTreeView<'a, Pubkey, TraderState> {
+        &self.traders
+    }
+}
+
+impl<'a> Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket> for FIFOMarketView<'a> {
+    fn get_data_size(&self) -> usize {
+        self.data_size
+    }
+    fn get_collected_fee_amount(&self) -> QuoteLots {
+        self.fields.collected_quote_lot_fees
+    }
+    fn get_uncollected_fee_amount(&self) -> QuoteLots {
+        self.fields.unclaimed_quote_lot_fees
+    }
+    fn get_taker_fee_bps(&self) -> u64 {
+        self.fields.taker_fee_bps
+    }
+    fn get_sequence_number(&self) -> u64 {
+        self.fields.order_sequence_number
+    }
+    fn get_tick_size(&self) -> u64 {
+        self.fields.tick_size_in_quote_lots_per_base_unit
+    }
+    fn get_book(&self, side: Side) -> &dyn OrderedNodeAllocatorMap<FIFOOrderId, FIFORestingOrder> {
+        self.get_book_view(side)
+    }
+    fn get_trader_index(&self, trader: &Pubkey) -> Option<u32> {
+        match self.traders.get_addr(trader) {
+            SENTINEL => None,
+            index => Some(index),
+        }
+    }
+    fn get_trader_id_from_index(&self, trader_index: u32) -> Option<Pubkey> {
+        self.traders.node(trader_index).ok().map(|node| node.key)
+    }
+    fn get_base_lots_per_base_unit(&self) -> u64 {
+        self.fields.base_lots_per_base_unit
+    }
+    fn get_registered_traders(&self) -> &dyn OrderedNodeAllocatorMap<Pubkey, TraderState> {
+        &self.traders
+    }
+}
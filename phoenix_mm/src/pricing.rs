@@ -0,0 +1,147 @@
+/*
+Avellaneda–Stoikov quoting in fixed point.
+
+Prices are expressed relative to the fair price, in bps, so the model parameters do not depend
+on the market's units:
+
+  q      inventory, in quote sizes, positive when long base (see get_inventory_in_quote_sizes)
+  gamma  risk aversion, per bps
+  kappa  decay of the order arrival intensity, per bps: fills get e times rarer every 1/kappa bps
+  sigma  volatility of the fair price over the quoting horizon, in bps
+
+  reservation price  r = s - q * gamma * sigma^2
+  half spread        d = gamma * sigma^2 / 2 + ln(1 + gamma / kappa) / gamma
+  bid, ask           r - d, r + d
+
+All intermediate values are FP_ONE scaled u128/i128, ln is evaluated with an atanh series.
+*/
+use crate::types::*;
+
+/// Fixed point scale of the model, 1e-12 resolution
+pub const FP_ONE: u128 = 1_000_000_000_000;
+/// gamma and kappa are configured in millionths
+pub const MILLIONTHS: u128 = 1_000_000;
+/// |q| is capped, a book this unbalanced is quoted as if it were at the cap
+pub const MAX_INVENTORY_IN_QUOTE_SIZES: i128 = 1_000_000;
+pub const MAX_RISK_AVERSION_IN_MILLIONTHS: u64 = 1_000_000_000;
+pub const MAX_VOLATILITY_IN_BPS: u64 = 10_000;
+// ln is evaluated at a finer scale: ln(1 + gamma / kappa) / gamma magnifies its error by 1 / gamma
+const LN_ONE: u128 = 1_000_000_000_000_000_000;
+const LN_2: u128 = 693_147_180_559_945_309;
+const BPS: u128 = 10_000;
+
+#[derive(Clone, Copy)]
+pub struct AvellanedaStoikov {
+    pub risk_aversion_in_millionths: u64,
+    pub order_arrival_decay_in_millionths: u64,
+    pub volatility_in_bps: u64,
+}
+
+impl AvellanedaStoikov {
+    /// q * gamma * sigma^2, in FP_ONE scaled bps, positive moves the reservation price down
+    pub fn reservation_offset_in_bps(&self, inventory_in_quote_sizes: i128) -> i128 {
+        let q = inventory_in_quote_sizes.clamp(
+            -MAX_INVENTORY_IN_QUOTE_SIZES * FP_ONE as i128,
+            MAX_INVENTORY_IN_QUOTE_SIZES * FP_ONE as i128,
+        );
+        q * self.variance_risk() as i128 / MILLIONTHS as i128
+    }
+
+    /// gamma * sigma^2 / 2 + ln(1 + gamma / kappa) / gamma, in FP_ONE scaled bps
+    pub fn half_spread_in_bps(&self) -> u128 {
+        let gamma = self.risk_aversion_in_millionths as u128;
+        let kappa = self.order_arrival_decay_in_millionths as u128;
+        let inventory_term = self.variance_risk() * FP_ONE / (2 * MILLIONTHS);
+        let arrival_term = if gamma == 0 {
+            // limit of ln(1 + gamma / kappa) / gamma
+            FP_ONE * MILLIONTHS / kappa
+        } else {
+            ln_1p_fine(gamma * LN_ONE / kappa) * MILLIONTHS / gamma / (LN_ONE / FP_ONE)
+        };
+        inventory_term + arrival_term
+    }
+
+    // gamma (millionths) * sigma^2
+    fn variance_risk(&self) -> u128 {
+        let sigma = self.volatility_in_bps as u128;
+        self.risk_aversion_in_millionths as u128 * sigma * sigma
+    }
+
+    /// Bid and ask edges from the fair price, in FP_ONE scaled bps, each within +-10_000 bps.
+    /// A negative edge quotes through the fair price (e.g. the ask of a long book).
+    pub fn get_edges_in_bps(&self, inventory_in_quote_sizes: i128) -> (i128, i128) {
+        let offset = self.reservation_offset_in_bps(inventory_in_quote_sizes);
+        let half_spread = self.half_spread_in_bps().min(BPS * FP_ONE) as i128;
+        let max_edge = (BPS * FP_ONE) as i128;
+        (
+            (half_spread + offset).clamp(-max_edge, max_edge),
+            (half_spread - offset).clamp(-max_edge, max_edge),
+        )
+    }
+
+    /// Bid and ask around `fair_price_in_ticks`, rounded away from the fair price and at least
+    /// one tick apart
+    pub fn get_prices_in_ticks(
+        &self,
+        fair_price_in_ticks: u64,
+        inventory_in_quote_sizes: i128,
+    ) -> (u64, u64) {
+        let (bid_edge, ask_edge) = self.get_edges_in_bps(inventory_in_quote_sizes);
+        let fair = fair_price_in_ticks as i128;
+        let scale = (BPS * FP_ONE) as i128;
+        // fair * (1 - bid_edge / 10_000) rounded down, fair * (1 + ask_edge / 10_000) rounded up
+        let bid = (fair * (scale - bid_edge)).div_euclid(scale).max(1) as u64;
+        let ask = -(-fair * (scale + ask_edge)).div_euclid(scale);
+        let ask = (ask.min(u64::MAX as i128) as u64).max(bid.saturating_add(1));
+        (bid, ask)
+    }
+}
+
+/// ln(1 + x) for FP_ONE scaled x >= 0
+pub fn ln_1p(x: u128) -> u128 {
+    ln_1p_fine(x * (LN_ONE / FP_ONE)) / (LN_ONE / FP_ONE)
+}
+
+// ln(1 + x) for LN_ONE scaled x >= 0, x below 2^128 / LN_ONE
+fn ln_1p_fine(x: u128) -> u128 {
+    // 1 + x = y * 2^k with y in [1, 2)
+    let mut y = LN_ONE + x;
+    let mut k = 0;
+    while y >= 2 * LN_ONE {
+        y /= 2;
+        k += 1;
+    }
+    // ln(y) = 2 * atanh(z) = 2 * (z + z^3 / 3 + z^5 / 5 + ...), z = (y - 1) / (y + 1) <= 1/3
+    let z = (y - LN_ONE) * LN_ONE / (y + LN_ONE);
+    let z2 = z * z / LN_ONE;
+    let mut term = z;
+    let mut sum = 0;
+    let mut n = 1;
+    while term > 0 {
+        sum += term / n;
+        term = term * z2 / LN_ONE;
+        n += 2;
+    }
+    2 * sum + k * LN_2
+}
+
+/// How far the trader is from holding half of its value in base, in quote sizes (FP_ONE scaled).
+/// Balances are in atoms, the fair price in quote atoms per raw base unit.
+pub fn get_inventory_in_quote_sizes(
+    header: &MarketHeader,
+    base_atoms: u64,
+    quote_atoms: u64,
+    fair_price_in_quote_atoms_per_raw_base_unit: u64,
+    quote_size_in_quote_atoms: u64,
+) -> i128 {
+    if quote_size_in_quote_atoms == 0 {
+        return 0;
+    }
+    let base_value_in_quote_atoms = base_atoms as u128
+        * fair_price_in_quote_atoms_per_raw_base_unit as u128
+        / 10u128.pow(header.base_params.decimals);
+    let excess =
+        (base_value_in_quote_atoms.min(i128::MAX as u128) as i128 - quote_atoms as i128) / 2;
+    let max_excess = MAX_INVENTORY_IN_QUOTE_SIZES * quote_size_in_quote_atoms as i128;
+    excess.clamp(-max_excess, max_excess) * FP_ONE as i128 / quote_size_in_quote_atoms as i128
+}
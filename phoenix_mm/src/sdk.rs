@@ -0,0 +1,164 @@
+/*
+Host side instruction builders for the phoenix_mm program (enabled by the `client` feature).
+Accounts are passed as named struct fields and instruction data is encoded by
+PhoenixMmInstruction, so account order and data layout are fixed here once.
+*/
+use crate::instruction::PhoenixMmInstruction;
+use crate::types::*;
+use crate::utils::PHONIEX_PROGRAM_ID;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::{Pubkey, pubkey};
+
+pub const PROGRAM_ID: Pubkey = Pubkey::new_from_array(crate::ID);
+pub const PHOENIX_PROGRAM_ID: Pubkey = Pubkey::new_from_array(PHONIEX_PROGRAM_ID);
+pub const PHOENIX_LOG_AUTHORITY: Pubkey = pubkey!("7aDTsspkQNGKmrexAN7FLx9oxU3iPczSSvHNggyuqYkR");
+pub const PHOENIX_SEAT_MANAGER_PROGRAM_ID: Pubkey =
+    pubkey!("PSMxQbAoDWDbvd9ezQJgARyq6R9L5kJAasaLDVcZwf1");
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+pub const SYSTEM_PROGRAM_ID: Pubkey = pubkey!("11111111111111111111111111111111");
+
+// Phoenix `CancelAllOrders`
+const PHOENIX_CANCEL_ALL_ORDERS_DISCRIMINANT: u8 = 6;
+
+pub fn get_strategy_address(trader: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"phoenix_strategy", trader.as_ref()], &PROGRAM_ID)
+}
+
+pub fn get_seat_address(market: &Pubkey, trader: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"seat", market.as_ref(), trader.as_ref()],
+        &PHOENIX_PROGRAM_ID,
+    )
+}
+
+pub fn get_seat_manager_address(market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[market.as_ref()], &PHOENIX_SEAT_MANAGER_PROGRAM_ID)
+}
+
+pub fn get_seat_deposit_collector_address(market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[market.as_ref(), b"deposit"],
+        &PHOENIX_SEAT_MANAGER_PROGRAM_ID,
+    )
+}
+
+pub fn get_associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Accounts of `update_quotes`, in instruction order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateQuotesAccounts {
+    pub strategy: Pubkey,
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    pub seat: Pubkey,
+    pub base_account: Pubkey,
+    pub quote_account: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+}
+impl UpdateQuotesAccounts {
+    /// Derive every account from the market header, using the trader's associated token accounts
+    pub fn new(market: &Pubkey, header: &MarketHeader, trader: &Pubkey) -> Self {
+        let base_mint = Pubkey::new_from_array(header.base_params.mint_key);
+        let quote_mint = Pubkey::new_from_array(header.quote_params.mint_key);
+        UpdateQuotesAccounts {
+            strategy: get_strategy_address(trader).0,
+            market: *market,
+            trader: *trader,
+            seat: get_seat_address(market, trader).0,
+            base_account: get_associated_token_address(trader, &base_mint),
+            quote_account: get_associated_token_address(trader, &quote_mint),
+            base_vault: Pubkey::new_from_array(header.base_params.vault_key),
+            quote_vault: Pubkey::new_from_array(header.quote_params.vault_key),
+        }
+    }
+
+    pub fn to_account_metas(&self) -> [AccountMeta; 11] {
+        [
+            AccountMeta::new(self.strategy, false),
+            AccountMeta::new(self.market, false),
+            AccountMeta::new(self.trader, true),
+            AccountMeta::new_readonly(PHOENIX_PROGRAM_ID, false),
+            AccountMeta::new_readonly(PHOENIX_LOG_AUTHORITY, false),
+            AccountMeta::new(self.seat, false),
+            AccountMeta::new(self.base_account, false),
+            AccountMeta::new(self.quote_account, false),
+            AccountMeta::new(self.base_vault, false),
+            AccountMeta::new(self.quote_vault, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ]
+    }
+}
+
+/// Creates the strategy account of `trader` for `market`
+pub fn initialize_ix(trader: &Pubkey, market: &Pubkey, params: &StrategyParams) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(get_strategy_address(trader).0, false),
+            AccountMeta::new(*trader, true),
+            AccountMeta::new_readonly(*market, false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        ],
+        data: PhoenixMmInstruction::Initialize(*params).pack().to_vec(),
+    }
+}
+
+/// Grows the strategy account of `trader` to the current layout, a no-op once it is current
+pub fn migrate_strategy_ix(trader: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(get_strategy_address(trader).0, false),
+            AccountMeta::new(*trader, true),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        ],
+        data: PhoenixMmInstruction::MigrateStrategy.pack().to_vec(),
+    }
+}
+
+/// Requotes around `fair_price_in_quote_atoms_per_raw_base_unit`, see `conversions`
+pub fn update_quotes_ix(
+    accounts: &UpdateQuotesAccounts,
+    fair_price_in_quote_atoms_per_raw_base_unit: u64,
+    params: &StrategyParams,
+) -> Instruction {
+    let order_params = OrderParams {
+        fair_price_in_quote_atoms_per_raw_base_unit,
+        strategy_params: *params,
+    };
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas().to_vec(),
+        data: PhoenixMmInstruction::UpdateQuotes(order_params)
+            .pack()
+            .to_vec(),
+    }
+}
+
+/// Phoenix `CancelAllOrders`, signed by the trader directly (no phoenix_mm involved)
+pub fn cancel_all_orders_ix(accounts: &UpdateQuotesAccounts) -> Instruction {
+    Instruction {
+        program_id: PHOENIX_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(PHOENIX_PROGRAM_ID, false),
+            AccountMeta::new_readonly(PHOENIX_LOG_AUTHORITY, false),
+            AccountMeta::new(accounts.market, false),
+            AccountMeta::new_readonly(accounts.trader, true),
+            AccountMeta::new(accounts.base_account, false),
+            AccountMeta::new(accounts.quote_account, false),
+            AccountMeta::new(accounts.base_vault, false),
+            AccountMeta::new(accounts.quote_vault, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ],
+        data: vec![PHOENIX_CANCEL_ALL_ORDERS_DISCRIMINANT],
+    }
+}
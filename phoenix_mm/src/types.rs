@@ -1,3 +1,5 @@
+use crate::book::{aggregate_levels, live_orders};
+use arrayvec::ArrayVec;
 use borsh::{BorshDeserialize, BorshSerialize};
 use bytemuck::{Pod, Zeroable};
 use core::fmt::Debug;
@@ -81,16 +83,106 @@ pub struct PhoenixStrategyState {
     pub post_only: u8,
     /// Determines whether/how to improve BBO
     pub price_improvement_behavior: u8,
+    /// If set to 1, quotes never get closer to fair price than taker fee + hedge cost
+    pub cover_taker_fee: u8,
+    pub hedge_cost_in_bps: u16,
+    /// Keep resting orders within this many ticks of the new quote, see `keep_remaining_in_pct`
+    pub requote_tolerance_in_ticks: u8,
+    /// 0 disables keeping orders that differ from the new quote
+    pub keep_remaining_in_pct: u8,
+    // Best bid/ask detection, see `BestPriceFilter`
+    pub min_best_size_in_pct: u8,
+    pub min_best_size_in_base_lots: u64,
+    pub best_price_depth_in_base_lots: u64,
+    /// Determines how the bid and ask are derived from the fair price
+    pub pricing_model: u8,
+    // Avellaneda-Stoikov parameters, see pricing.rs
+    pub risk_aversion_in_millionths: u64,
+    pub order_arrival_decay_in_millionths: u64,
+    /// Volatility estimate used by the model
+    pub volatility_in_bps: u64,
+    // EWMA volatility estimator, see volatility.rs
+    pub volatility_span_in_slots: u64,
+    pub volatility_edge_multiplier_in_hundredths: u16,
+    /// Fair price of the previous update_quotes
+    pub last_fair_price_in_quote_atoms_per_raw_base_unit: u64,
+    /// EWMA variance of fair price returns per slot, in millionths of bps^2
+    pub fair_price_variance: u64,
+    /// Layout version, `STRATEGY_STATE_VERSION`. New fields are appended after it
+    pub version: u8,
+}
+
+/// Current layout of `PhoenixStrategyState`, bumped whenever fields are appended
+pub const STRATEGY_STATE_VERSION: u8 = 1;
+/// Sizes of strategy accounts created before the layout was versioned, oldest first. Fields
+/// were only ever appended (the first ones into the padding of the deployed layout), so each
+/// one is a prefix of the current layout and `MigrateStrategy` grows it in place.
+pub const LEGACY_STRATEGY_STATE_SIZES: [usize; 1] = [152];
+const _: () = assert!(LEGACY_STRATEGY_STATE_SIZES[0] == size_of::<LegacyPhoenixStrategyState>());
+// the fields appended after the deployed layout start in its padding
+const _: () = assert!(
+    core::mem::offset_of!(LegacyPhoenixStrategyState, padding)
+        == core::mem::offset_of!(PhoenixStrategyState, cover_taker_fee)
+);
+
+/// `PhoenixStrategyState` as first deployed, before the layout was versioned
+#[repr(C, packed)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct LegacyPhoenixStrategyState {
+    pub trader: Pubkey,
+    pub market: Pubkey,
+    pub bid_order_sequence_number: u64,
+    pub bid_price_in_ticks: u64,
+    pub initial_bid_size_in_base_lots: u64,
+    pub ask_order_sequence_number: u64,
+    pub ask_price_in_ticks: u64,
+    pub initial_ask_size_in_base_lots: u64,
+    pub last_update_slot: u64,
+    pub last_update_unix_timestamp: i64,
+    pub quote_edge_in_bps: u64,
+    pub quote_size_in_quote_atoms: u64,
+    pub post_only: u8,
+    pub price_improvement_behavior: u8,
     pub padding: [u8; 6],
 }
 
+impl PhoenixStrategyState {
+    /// Current layout of a legacy strategy account. The fields it lacks are zeroed, which
+    /// leaves each of their features disabled until the next update_quotes sets them.
+    pub fn from_legacy_bytes(data: &[u8]) -> Option<Self> {
+        if !LEGACY_STRATEGY_STATE_SIZES.contains(&data.len()) {
+            return None;
+        }
+        let legacy: LegacyPhoenixStrategyState =
+            *bytemuck::try_from_bytes(data.get(..size_of::<LegacyPhoenixStrategyState>())?).ok()?;
+        Some(PhoenixStrategyState {
+            trader: legacy.trader,
+            market: legacy.market,
+            bid_order_sequence_number: legacy.bid_order_sequence_number,
+            bid_price_in_ticks: legacy.bid_price_in_ticks,
+            initial_bid_size_in_base_lots: legacy.initial_bid_size_in_base_lots,
+            ask_order_sequence_number: legacy.ask_order_sequence_number,
+            ask_price_in_ticks: legacy.ask_price_in_ticks,
+            initial_ask_size_in_base_lots: legacy.initial_ask_size_in_base_lots,
+            last_update_slot: legacy.last_update_slot,
+            last_update_unix_timestamp: legacy.last_update_unix_timestamp,
+            quote_edge_in_bps: legacy.quote_edge_in_bps,
+            quote_size_in_quote_atoms: legacy.quote_size_in_quote_atoms,
+            post_only: legacy.post_only,
+            price_improvement_behavior: legacy.price_improvement_behavior,
+            version: STRATEGY_STATE_VERSION,
+            ..Zeroable::zeroed()
+        })
+    }
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Copy, Clone, PartialEq, Eq)]
 pub enum SelfTradeBehavior {
     Abort,
     CancelProvide,
     DecrementTake,
 }
-#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Eq)]
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Side {
     Bid,
     Ask,
@@ -200,7 +292,32 @@ pub struct StrategyParams {
     pub quote_size_in_quote_atoms: u64,
     pub price_improvement_behavior: u8, //0 ->join,1->Dime,2->Ignore
     pub post_only: u8,
-    pub padding: [u8; 6],
+    /// If set to 1, quotes never get closer to fair price than taker fee + hedge cost
+    pub cover_taker_fee: u8,
+    /// Cost of hedging a fill elsewhere, added to the taker fee for the minimum edge
+    pub hedge_cost_in_bps: u16,
+    /// A resting order this close to its new price may be kept to preserve queue priority
+    pub requote_tolerance_in_ticks: u8,
+    /// Minimum share of the initial size left for a resting order to be kept, 0 disables keeping
+    pub keep_remaining_in_pct: u8,
+    /// Levels smaller than this share (%) of our quote size are ignored when joining/diming
+    pub min_best_size_in_pct: u8,
+    /// Levels smaller than this are ignored when joining/diming
+    pub min_best_size_in_base_lots: u64,
+    /// If non zero, join/dime the size-weighted price of this many base lots instead of the top level
+    pub best_price_depth_in_base_lots: u64,
+    /// 0 -> fixed edge, 1 -> Avellaneda-Stoikov, see `PricingModel`
+    pub pricing_model: u8,
+    /// Avellaneda-Stoikov gamma, per bps, in millionths
+    pub risk_aversion_in_millionths: u64,
+    /// Avellaneda-Stoikov kappa, per bps, in millionths
+    pub order_arrival_decay_in_millionths: u64,
+    /// Volatility of the fair price over the quoting horizon
+    pub volatility_in_bps: u64,
+    /// Span of the EWMA variance of fair price returns, 0 disables the estimator
+    pub volatility_span_in_slots: u64,
+    /// The edge is at least this many (hundredths) EWMA sigmas
+    pub volatility_edge_multiplier_in_hundredths: u16,
 }
 #[repr(C, packed)]
 #[derive(Clone, Copy, Pod, Zeroable)]
@@ -216,7 +333,7 @@ pub struct Ticks {
 }
 
 #[repr(C)]
-#[derive(Eq, PartialEq, Default, Copy, Clone, Debug, Zeroable, Pod, PartialOrd, Ord)]
+#[derive(Eq, PartialEq, Default, Copy, Clone, Debug, Zeroable, Pod)]
 pub struct FIFOOrderId {
     /// The price of the order, in ticks. Each market has a designated
     /// tick size (some number of quote lots per base unit) that is used to convert the price to ticks.
@@ -237,6 +354,24 @@ pub struct FIFOOrderId {
 }
 
 impl OrderId for FIFOOrderId {}
+/// Same ordering as Phoenix: bids by descending price, asks by ascending price, then time
+/// priority. Lookups in the book trees rely on it, and it makes in-order iteration best first.
+impl Ord for FIFOOrderId {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        match Side::from_order_sequence_number(self.order_sequence_number) {
+            // bid sequence numbers are inverted, so the earlier order has the larger one
+            Side::Bid => (other.price_in_ticks, other.order_sequence_number)
+                .cmp(&(self.price_in_ticks, self.order_sequence_number)),
+            Side::Ask => (self.price_in_ticks, self.order_sequence_number)
+                .cmp(&(other.price_in_ticks, other.order_sequence_number)),
+        }
+    }
+}
+impl PartialOrd for FIFOOrderId {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
 impl FIFOOrderId {
     pub fn new_from_untyped(price_in_ticks: u64, order_sequence_number: u64) -> Self {
         FIFOOrderId {
@@ -365,6 +500,21 @@ impl<
 > Market<MarketTraderId, FIFOOrderId, FIFORestingOrder, OrderPacket>
     for FIFOMarket<MarketTraderId, BIDS_SIZE, ASKS_SIZE, NUM_SEATS>
 {
+    fn get_data_size(&self) -> usize {
+        size_of::<Self>()
+    }
+    fn get_collected_fee_amount(&self) -> QuoteLots {
+        self.collected_quote_lot_fees
+    }
+    fn get_uncollected_fee_amount(&self) -> QuoteLots {
+        self.unclaimed_quote_lot_fees
+    }
+    fn get_taker_fee_bps(&self) -> u64 {
+        self.taker_fee_bps
+    }
+    fn get_sequence_number(&self) -> u64 {
+        self.order_sequence_number
+    }
     fn get_base_lots_per_base_unit(&self) -> u64 {
         self.base_lots_per_base_unit
     }
@@ -384,6 +534,12 @@ impl<
         let addr = self.traders.get_addr(trader_id);
         if addr == SENTINEL { None } else { Some(addr) }
     }
+    fn get_trader_id_from_index(&self, trader_index: u32) -> Option<MarketTraderId> {
+        if trader_index == SENTINEL || trader_index as usize > NUM_SEATS {
+            return None;
+        }
+        Some(self.traders.get_node(trader_index).key)
+    }
     fn get_registered_traders(&self) -> &dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState> {
         &self.traders as &dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState>
     }
@@ -411,6 +567,26 @@ impl<
     ) -> &mut dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState> {
         &mut self.traders as &mut dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState>
     }
+    fn get_book_mut(
+        &mut self,
+        side: Side,
+    ) -> &mut dyn OrderedNodeAllocatorMap<FIFOOrderId, FIFORestingOrder> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+    fn initialize_with_params(
+        &mut self,
+        tick_size_in_quote_lots_per_base_unit: u64,
+        base_lots_per_base_unit: u64,
+    ) {
+        self.bids.initialize();
+        self.asks.initialize();
+        self.traders.initialize();
+        self.tick_size_in_quote_lots_per_base_unit = tick_size_in_quote_lots_per_base_unit;
+        self.base_lots_per_base_unit = base_lots_per_base_unit;
+    }
 }
 
 pub struct MarketWrapper<'a, MarketTraderId, MarketOrderId, MarketRestingOrder, MarketOrderPacket> {
@@ -432,15 +608,16 @@ pub trait Market<
     MarketOrderPacket: OrderPacketMetadata,
 >
 {
-    fn get_data_size(&self) -> usize {
-        unimplemented!()
-    }
-    fn get_collected_fee_amount(&self) -> QuoteLots {
-        unimplemented!()
-    }
-    fn get_uncollected_fee_amount(&self) -> QuoteLots {
-        unimplemented!()
-    }
+    /// Size of the market body, header excluded
+    fn get_data_size(&self) -> usize;
+    /// Fees collected over the lifetime of the market
+    fn get_collected_fee_amount(&self) -> QuoteLots;
+    /// Fees accrued to the market and not claimed yet
+    fn get_uncollected_fee_amount(&self) -> QuoteLots;
+    /// Fee charged to takers on the quote lots they trade
+    fn get_taker_fee_bps(&self) -> u64;
+    /// Sequence number the next order placed on the market receives
+    fn get_sequence_number(&self) -> u64;
     fn get_tick_size(&self) -> u64;
 
     fn get_book(
@@ -448,6 +625,8 @@ pub trait Market<
         side: Side,
     ) -> &dyn OrderedNodeAllocatorMap<MarketOrderId, MarketRestingOrder>;
     fn get_trader_index(&self, trader: &MarketTraderId) -> Option<u32>;
+    /// Inverse of `get_trader_index`, resolves `FIFORestingOrder::trader_index`
+    fn get_trader_id_from_index(&self, trader_index: u32) -> Option<MarketTraderId>;
     fn get_base_lots_per_base_unit(&self) -> u64;
     fn get_registered_traders(&self) -> &dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState>;
 }
@@ -461,6 +640,16 @@ pub trait WritableMarket<
     fn get_registered_traders_mut(
         &mut self,
     ) -> &mut dyn OrderedNodeAllocatorMap<MarketTraderId, TraderState>;
+    fn get_book_mut(
+        &mut self,
+        side: Side,
+    ) -> &mut dyn OrderedNodeAllocatorMap<MarketOrderId, MarketRestingOrder>;
+    /// Set up an all-zero market: empty trees and lot/tick parameters (host side fixtures)
+    fn initialize_with_params(
+        &mut self,
+        tick_size_in_quote_lots_per_base_unit: u64,
+        base_lots_per_base_unit: u64,
+    );
     fn get_or_register_trader(&mut self, trader: &MarketTraderId) -> Option<u32> {
         let registered_traders = self.get_registered_traders_mut();
         if !registered_traders.contains(trader) {
@@ -494,26 +683,105 @@ impl<'a, MarketTraderId, MarketOrderPacket, MarketRestingOrder, MarketOrderId>
         Self { inner: market }
     }
 }
+/// Which levels count as the best bid/ask, the default takes the first level as is
+#[derive(Default, Clone, Copy)]
+pub struct BestPriceFilter {
+    /// Levels smaller than this are skipped (dust)
+    pub min_level_size_in_base_lots: u64,
+    /// If non zero, the best price is the size-weighted price of the first this many base lots
+    pub depth_in_base_lots: u64,
+}
+
 pub fn get_best_bid_and_ask(
     market: &dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
     trader_index: u64,
+    current_slot: u64,
+    current_unix_timestamp_in_seconds: u64,
+    filter: &BestPriceFilter,
 ) -> (u64, u64) {
-    let best_bid = market
-        .get_book(Side::Bid) // Get the bid order book
-        .iter() // Iterate through all bids
-        .find(|(_, o)| o.trader_index != trader_index) // Find the first order NOT from this trader
-        .map(|(o, _)| o.price_in_ticks.inner) // Get the price in ticks
-        .unwrap_or_else(|| 1); // Default to 1 if no order is found
-
-    let best_ask = market
-        .get_book(Side::Ask) // Get the ask order book
-        .iter() // Iterate through all asks
-        .find(|(_, o)| o.trader_index != trader_index) // Skip your own orders
-        .map(|(o, _)| o.price_in_ticks.inner) // Extract the price
-        .unwrap_or_else(|| u64::MAX); // Default to max if no order is found
+    let best_price = |side| {
+        get_best_price(
+            market,
+            side,
+            trader_index,
+            current_slot,
+            current_unix_timestamp_in_seconds,
+            filter,
+        )
+    };
+    let best_bid = best_price(Side::Bid).unwrap_or(1); // Default to 1 if no order is found
+    let best_ask = best_price(Side::Ask).unwrap_or(u64::MAX); // Default to max if no order is found
 
     (best_bid, best_ask)
 }
+
+/// Best price of `side` from other traders' live orders, filtered by `filter`.
+/// A size-weighted bid rounds down and ask rounds up, both away from the spread.
+pub fn get_best_price(
+    market: &dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    side: Side,
+    trader_index: u64,
+    current_slot: u64,
+    current_unix_timestamp_in_seconds: u64,
+    filter: &BestPriceFilter,
+) -> Option<u64> {
+    let mut weighted = WeightedPrice {
+        side,
+        filter,
+        notional: 0,
+        size_in_base_lots: 0,
+    };
+    let orders = live_orders(
+        market,
+        side,
+        current_slot,
+        current_unix_timestamp_in_seconds,
+    )
+    .filter(|(_, order)| order.trader_index != trader_index);
+    for level in aggregate_levels(orders) {
+        if let Some(price) = weighted.add_level(level.price_in_ticks, level.size_in_base_lots) {
+            return Some(price);
+        }
+    }
+    // book thinner than the depth: weight what is there
+    weighted.price()
+}
+
+struct WeightedPrice<'a> {
+    side: Side,
+    filter: &'a BestPriceFilter,
+    notional: u128,
+    size_in_base_lots: u64,
+}
+impl WeightedPrice<'_> {
+    /// Some(best price) once enough size has been seen
+    fn add_level(&mut self, price_in_ticks: u64, size_in_base_lots: u64) -> Option<u64> {
+        if size_in_base_lots < self.filter.min_level_size_in_base_lots {
+            return None;
+        }
+        if self.filter.depth_in_base_lots == 0 {
+            return Some(price_in_ticks);
+        }
+        let take = size_in_base_lots.min(self.filter.depth_in_base_lots - self.size_in_base_lots);
+        self.notional += price_in_ticks as u128 * take as u128;
+        self.size_in_base_lots += take;
+        if self.size_in_base_lots == self.filter.depth_in_base_lots {
+            return self.price();
+        }
+        None
+    }
+
+    fn price(&self) -> Option<u64> {
+        if self.size_in_base_lots == 0 {
+            return None;
+        }
+        let size = self.size_in_base_lots as u128;
+        Some(match self.side {
+            Side::Bid => self.notional / size,
+            Side::Ask => self.notional.div_ceil(size),
+        } as u64)
+    }
+}
 pub enum PriceImprovementBehavior {
     Join,
     Dime,
@@ -537,9 +805,51 @@ impl PriceImprovementBehavior {
         }
     }
 }
-#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub enum PricingModel {
+    FixedEdge,
+    AvellanedaStoikov,
+}
+impl PricingModel {
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            PricingModel::FixedEdge => 0,
+            PricingModel::AvellanedaStoikov => 1,
+        }
+    }
+
+    pub fn from_u8(byte: u8) -> Self {
+        match byte {
+            0 => PricingModel::FixedEdge,
+            1 => PricingModel::AvellanedaStoikov,
+            _ => panic!("Invalid PricingModel"),
+        }
+    }
+}
+/// update_quotes cancels at most its bid and its ask
+pub const MAX_CANCEL_ORDERS: usize = 2;
+/// update_quotes places at most one order per side
+pub const MAX_ORDERS_PER_SIDE: usize = 1;
+
+/// Borsh encodes Vec<T> as a u32 length followed by the elements, ArrayVec is written the same way
+fn serialize_array_vec<T: BorshSerialize, W: std::io::Write, const N: usize>(
+    items: &ArrayVec<T, N>,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    (items.len() as u32).serialize(writer)?;
+    for item in items {
+        item.serialize(writer)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
 pub struct CancelMultipleOrdersByIdParams {
-    pub orders: Vec<CancelOrderParams>,
+    pub orders: ArrayVec<CancelOrderParams, MAX_CANCEL_ORDERS>,
+}
+impl BorshSerialize for CancelMultipleOrdersByIdParams {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        serialize_array_vec(&self.orders, writer)
+    }
 }
 #[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
 pub struct CancelOrderParams {
@@ -584,18 +894,25 @@ impl CondensedOrder {
     }
 }
 /// Struct to send a vector of bids and asks as PostOnly orders in a single packet.
-#[derive(BorshDeserialize, BorshSerialize)]
 pub struct MultipleOrderPacket {
     /// Bids and asks are in the format (price in ticks, size in base lots)
-    pub bids: Vec<CondensedOrder>,
-    pub asks: Vec<CondensedOrder>,
+    pub bids: ArrayVec<CondensedOrder, MAX_ORDERS_PER_SIDE>,
+    pub asks: ArrayVec<CondensedOrder, MAX_ORDERS_PER_SIDE>,
     pub client_order_id: Option<u128>,
     pub failed_multiple_limit_order_behavior: FailedMultipleLimitOrderBehavior,
 }
+impl BorshSerialize for MultipleOrderPacket {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        serialize_array_vec(&self.bids, writer)?;
+        serialize_array_vec(&self.asks, writer)?;
+        self.client_order_id.serialize(writer)?;
+        self.failed_multiple_limit_order_behavior.serialize(writer)
+    }
+}
 impl MultipleOrderPacket {
     pub fn new(
-        bids: Vec<CondensedOrder>,
-        asks: Vec<CondensedOrder>,
+        bids: ArrayVec<CondensedOrder, MAX_ORDERS_PER_SIDE>,
+        asks: ArrayVec<CondensedOrder, MAX_ORDERS_PER_SIDE>,
         client_order_id: Option<u128>,
         reject_post_only: bool,
     ) -> Self {
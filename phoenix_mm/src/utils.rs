@@ -1,5 +1,10 @@
+use crate::conversions::*;
+use crate::error::PhoenixMmError;
+use crate::market_view::FIFOMarketView;
 use crate::types::*;
+use arrayvec::ArrayVec;
 use borsh::BorshSerialize;
+use core::ops::Deref;
 use pinocchio::{
     ProgramResult,
     account_info::AccountInfo,
@@ -31,8 +36,26 @@ macro_rules! fifo_market {
     };
 }
 
+/// Phoenix instruction data is a one byte tag followed by the Borsh payload
+pub const MAX_PHOENIX_INSTRUCTION_LEN: usize = 256;
+/// Return data holds at most 1024 bytes of (price_in_ticks, order_sequence_number) pairs
+pub const MAX_RETURNED_ORDER_IDS: usize = 1024 / 16;
+
+/// Serialize a Phoenix instruction into a stack buffer
+pub fn phoenix_instruction_data(
+    tag: u8,
+    payload: &impl BorshSerialize,
+) -> Result<ArrayVec<u8, MAX_PHOENIX_INSTRUCTION_LEN>, ProgramError> {
+    let mut data = ArrayVec::new();
+    data.push(tag);
+    payload
+        .serialize(&mut data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    Ok(data)
+}
+
 pub fn parse_order_ids_from_return_data(
-    order_ids: &mut Vec<FIFOOrderId>,
+    order_ids: &mut ArrayVec<FIFOOrderId, MAX_RETURNED_ORDER_IDS>,
 ) -> Result<(), ProgramError> {
     if let Some((return_data)) = get_return_data() {
         msg!("Found return data");
@@ -43,10 +66,12 @@ pub fn parse_order_ids_from_return_data(
                 // Read 8 bytes each
                 let price_in_ticks = u64::from_le_bytes(chunk[0..8].try_into().unwrap());
                 let order_sequence_number = u64::from_le_bytes(chunk[8..16].try_into().unwrap());
-                order_ids.push(FIFOOrderId::new_from_untyped(
-                    price_in_ticks,
-                    order_sequence_number,
-                ))
+                order_ids
+                    .try_push(FIFOOrderId::new_from_untyped(
+                        price_in_ticks,
+                        order_sequence_number,
+                    ))
+                    .map_err(|_| PhoenixMmError::TooManyReturnedOrderIds)?;
             }
         } else {
             msg!("No orders in return data");
@@ -62,10 +87,74 @@ pub fn deserialize_market_header(data: &[u8]) -> Result<MarketHeader, ProgramErr
 
     Ok(*header)
 }
+/// (bids_size, asks_size, num_seats) of every market layout `deserialize_market` supports,
+/// other sizes can be read through `market_view::FIFOMarketView`
+pub const SUPPORTED_MARKET_SIZES: [(u64, u64, u64); 12] = [
+    (512, 512, 128),
+    (512, 512, 1025),
+    (512, 512, 1153),
+    (1024, 1024, 128),
+    (1024, 1024, 2049),
+    (1024, 1024, 2177),
+    (2048, 2048, 128),
+    (2048, 2048, 4097),
+    (2048, 2048, 4225),
+    (4096, 4096, 128),
+    (4096, 4096, 8193),
+    (4096, 4096, 8321),
+];
+
+macro_rules! fifo_market_size {
+    ($num_bids:literal, $num_asks:literal, $num_seats:literal) => {
+        size_of::<FIFOMarket<Pubkey, $num_bids, $num_asks, $num_seats>>()
+    };
+}
+
+/// Account size (header included) of a market with these size params
+pub fn get_market_size(market_size_params: &MarketSizeParams) -> Result<usize, ProgramError> {
+    let market_size = match (
+        market_size_params.bids_size,
+        market_size_params.asks_size,
+        market_size_params.num_seats,
+    ) {
+        (512, 512, 128) => fifo_market_size!(512, 512, 128),
+        (512, 512, 1025) => fifo_market_size!(512, 512, 1025),
+        (512, 512, 1153) => fifo_market_size!(512, 512, 1153),
+        (1024, 1024, 128) => fifo_market_size!(1024, 1024, 128),
+        (1024, 1024, 2049) => fifo_market_size!(1024, 1024, 2049),
+        (1024, 1024, 2177) => fifo_market_size!(1024, 1024, 2177),
+        (2048, 2048, 128) => fifo_market_size!(2048, 2048, 128),
+        (2048, 2048, 4097) => fifo_market_size!(2048, 2048, 4097),
+        (2048, 2048, 4225) => fifo_market_size!(2048, 2048, 4225),
+        (4096, 4096, 128) => fifo_market_size!(4096, 4096, 128),
+        (4096, 4096, 8193) => fifo_market_size!(4096, 4096, 8193),
+        (4096, 4096, 8321) => fifo_market_size!(4096, 4096, 8321),
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    Ok(size_of::<MarketHeader>() + market_size)
+}
+/// Market returned by `deserialize_market`, derefs to `dyn Market`
+pub enum MarketRef<'a> {
+    /// Monomorphized FIFOMarket of one of SUPPORTED_MARKET_SIZES
+    Fifo(&'a dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>),
+    /// Any other size, read through runtime offsets
+    View(FIFOMarketView<'a>),
+}
+impl<'a> Deref for MarketRef<'a> {
+    type Target = dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket> + 'a;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            MarketRef::Fifo(market) => *market,
+            MarketRef::View(market) => market,
+        }
+    }
+}
 pub fn deserialize_market<'a>(
     market_bytes: &'a [u8],
     market_size_params: &'a MarketSizeParams,
-) -> Result<&'a dyn Market<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>, ProgramError> {
+) -> Result<MarketRef<'a>, ProgramError> {
+    let account_bytes = market_bytes;
     let (_, market_bytes) = market_bytes.split_at(size_of::<MarketHeader>());
 
     let market = match (
@@ -86,11 +175,15 @@ pub fn deserialize_market<'a>(
         (4096, 4096, 8193) => fifo_market!(4096, 4096, 8193, market_bytes),
         (4096, 4096, 8321) => fifo_market!(4096, 4096, 8321, market_bytes),
         _ => {
-            //    phoenix_log!("Invalid parameters for market");
-            return Err(ProgramError::InvalidInstructionData);
+            return Ok(MarketRef::View(FIFOMarketView::load(
+                account_bytes,
+                market_size_params,
+            )?));
         }
     };
-    Ok(MarketWrapper::<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>::new(market).inner)
+    Ok(MarketRef::Fifo(
+        MarketWrapper::<Pubkey, FIFOOrderId, FIFORestingOrder, OrderPacket>::new(market).inner,
+    ))
 }
 pub fn deserialize_market_mut<'a>(
     market_bytes: &'a mut [u8],
@@ -132,11 +225,7 @@ pub fn create_cancel_multiple_orders_by_id_with_free_funds_instruction(
     trader: &AccountInfo,
     params: &CancelMultipleOrdersByIdParams,
 ) -> ProgramResult {
-    let data = [
-        (11 as u8).try_to_vec().unwrap(),
-        params.try_to_vec().unwrap(),
-    ]
-    .concat();
+    let data = phoenix_instruction_data(11, params)?;
     let account_metas = [
         AccountMeta::new(phoniex_program.key(), false, false), // phoenix program
         AccountMeta::new(phoenix_log_authority.key(), false, false), // log authority
@@ -181,11 +270,7 @@ pub fn create_new_order_with_custom_token_accounts(
                 AccountMeta::new(&quote_vault.key(), true, false),
                 AccountMeta::new(token_program.key(), false, false),
             ],
-            data: &[
-                (0 as u8).try_to_vec().unwrap(),
-                order_packet.try_to_vec().unwrap(),
-            ]
-            .concat(),
+            data: &phoenix_instruction_data(0, order_packet)?,
         };
         slice_invoke(
             &ix,
@@ -216,11 +301,7 @@ pub fn create_new_order_with_custom_token_accounts(
                 AccountMeta::new(&quote_vault.key(), true, false),
                 AccountMeta::new(token_program.key(), false, false),
             ],
-            data: &[
-                (2 as u8).try_to_vec().unwrap(),
-                order_packet.try_to_vec().unwrap(),
-            ]
-            .concat(),
+            data: &phoenix_instruction_data(2, order_packet)?,
         };
         slice_invoke(
             &ix,
@@ -255,11 +336,7 @@ pub fn create_new_multiple_order_with_custom_token_accounts(
 ) -> ProgramResult {
     //cpi to place multipule post only orders
 
-    let data = [
-        (16 as u8).try_to_vec().unwrap(),
-        multiple_order_packet.try_to_vec().unwrap(),
-    ]
-    .concat();
+    let data = phoenix_instruction_data(16, multiple_order_packet)?;
 
     let account_metas = [
         AccountMeta::new(phoniex_program.key(), false, false), // phoenix program
@@ -301,11 +378,85 @@ pub fn get_bid_price_in_ticks(
     header: &MarketHeader,
     edge_in_bps: u64,
 ) -> u64 {
-    let fair_price_in_ticks = fair_price_in_quote_atoms_per_raw_base_unit
-        * header.raw_base_units_per_base_unit as u64
-        / header.tick_size_in_quote_atoms_per_base_unit;
-    let edge_in_ticks = edge_in_bps * fair_price_in_ticks / 10_000;
-    fair_price_in_ticks - edge_in_ticks
+    let fair_price_in_ticks =
+        quote_atoms_per_raw_base_unit_to_ticks(header, fair_price_in_quote_atoms_per_raw_base_unit);
+    let edge_in_ticks = edge_in_bps.saturating_mul(fair_price_in_ticks) / 10_000;
+    // an edge of 100% or more leaves no valid bid, update_quotes skips a bid of 0 or 1
+    fair_price_in_ticks.saturating_sub(edge_in_ticks)
+}
+
+/// Edge that pays for hedging a fill: the taker fee plus the hedge cost
+pub fn get_min_edge_in_bps(taker_fee_bps: u64, hedge_cost_in_bps: u16) -> u64 {
+    taker_fee_bps + hedge_cost_in_bps as u64
+}
+
+/// Highest bid and lowest ask that still earn `min_edge_in_bps`, rounded away from fair price
+pub fn get_break_even_prices_in_ticks(
+    fair_price_in_quote_atoms_per_raw_base_unit: u64,
+    header: &MarketHeader,
+    min_edge_in_bps: u64,
+) -> (u64, u64) {
+    let fair_price_in_ticks =
+        quote_atoms_per_raw_base_unit_to_ticks(header, fair_price_in_quote_atoms_per_raw_base_unit);
+    let edge_in_ticks = (min_edge_in_bps * fair_price_in_ticks).div_ceil(10_000);
+    (
+        fair_price_in_ticks.saturating_sub(edge_in_ticks),
+        fair_price_in_ticks.saturating_add(edge_in_ticks),
+    )
+}
+
+/// Pull quotes back to break-even if Join/Dime (or rounding) moved them inside it
+pub fn clamp_to_break_even(
+    bid_price_in_ticks: u64,
+    ask_price_in_ticks: u64,
+    break_even_bid: u64,
+    break_even_ask: u64,
+) -> (u64, u64) {
+    (
+        bid_price_in_ticks.min(break_even_bid),
+        ask_price_in_ticks.max(break_even_ask),
+    )
+}
+
+/// Requote tolerance of the strategy, see `StrategyParams::keep_remaining_in_pct`
+pub struct RequoteTolerance {
+    pub in_ticks: u64,
+    pub min_remaining_in_pct: u64,
+}
+
+/// A resting order that is not identical to the new quote
+pub struct RestingQuote {
+    pub price_in_ticks: u64,
+    pub remaining_in_base_lots: u64,
+    pub initial_in_base_lots: u64,
+    /// Size with time priority over our order at its level
+    pub base_lots_ahead: u64,
+}
+
+/// Keep `resting` instead of requoting at `target_price_in_ticks` when it is close enough, still
+/// has enough size left, and no more size is ahead of it than a new order would queue behind
+/// (`base_lots_at_target`)
+pub fn keep_resting_quote(
+    tolerance: &RequoteTolerance,
+    resting: &RestingQuote,
+    target_price_in_ticks: u64,
+    base_lots_at_target: u64,
+) -> bool {
+    tolerance.min_remaining_in_pct > 0
+        && resting.price_in_ticks.abs_diff(target_price_in_ticks) <= tolerance.in_ticks
+        && resting.remaining_in_base_lots as u128 * 100
+            >= resting.initial_in_base_lots as u128 * tolerance.min_remaining_in_pct as u128
+        && resting.base_lots_ahead <= base_lots_at_target
+}
+
+/// Dust threshold for the best bid/ask: the larger of a fixed size and a share of our own size
+pub fn get_min_level_size_in_base_lots(
+    min_size_in_base_lots: u64,
+    min_size_in_pct: u8,
+    quote_size_in_base_lots: u64,
+) -> u64 {
+    let relative = (quote_size_in_base_lots as u128 * min_size_in_pct as u128 / 100) as u64;
+    min_size_in_base_lots.max(relative)
 }
 
 pub fn get_ask_price_in_ticks(
@@ -313,9 +464,87 @@ pub fn get_ask_price_in_ticks(
     header: &MarketHeader,
     edge_in_bps: u64,
 ) -> u64 {
-    let fair_price_in_ticks = fair_price_in_quote_atoms_per_raw_base_unit
-        * header.raw_base_units_per_base_unit as u64
-        / header.tick_size_in_quote_atoms_per_base_unit;
-    let edge_in_ticks = edge_in_bps * fair_price_in_ticks / 10_000;
-    fair_price_in_ticks + edge_in_ticks
+    let fair_price_in_ticks =
+        quote_atoms_per_raw_base_unit_to_ticks(header, fair_price_in_quote_atoms_per_raw_base_unit);
+    let edge_in_ticks = edge_in_bps.saturating_mul(fair_price_in_ticks) / 10_000;
+    fair_price_in_ticks.saturating_add(edge_in_ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    // one tick per quote atom per raw base unit, so fair prices are already in ticks
+    fn header() -> MarketHeader {
+        let mut header = MarketHeader::zeroed();
+        header.raw_base_units_per_base_unit = 1;
+        header.tick_size_in_quote_atoms_per_base_unit = 1;
+        header
+    }
+
+    #[test]
+    fn min_edge_adds_taker_fee_and_hedge_cost() {
+        assert_eq!(get_min_edge_in_bps(0, 0), 0);
+        assert_eq!(get_min_edge_in_bps(5, 0), 5);
+        assert_eq!(get_min_edge_in_bps(0, 3), 3);
+        assert_eq!(get_min_edge_in_bps(5, 3), 8);
+        assert_eq!(get_min_edge_in_bps(10_000, 9_999), 19_999);
+    }
+
+    #[test]
+    fn break_even_prices_round_away_from_fair_price() {
+        let header = header();
+        // exact: 15 bps of 10_000 ticks
+        assert_eq!(
+            get_break_even_prices_in_ticks(10_000, &header, 15),
+            (9_985, 10_015)
+        );
+        // 7 bps of 12_345 ticks is 8.64 ticks, rounded up to 9 on both sides
+        assert_eq!(
+            get_break_even_prices_in_ticks(12_345, &header, 7),
+            (12_336, 12_354)
+        );
+        // while a quote with the same edge only moves 8 ticks
+        assert_eq!(get_bid_price_in_ticks(12_345, &header, 7), 12_337);
+        assert_eq!(get_ask_price_in_ticks(12_345, &header, 7), 12_353);
+        // any edge on a tiny price is at least one tick
+        assert_eq!(get_break_even_prices_in_ticks(10, &header, 1), (9, 11));
+        assert_eq!(get_break_even_prices_in_ticks(10, &header, 0), (10, 10));
+        // an edge of more than 100% leaves no bid
+        assert_eq!(get_break_even_prices_in_ticks(10, &header, 20_000), (0, 30));
+    }
+
+    #[test]
+    fn break_even_prices_of_fee_and_hedge_cost() {
+        let header = header();
+        for (taker_fee_bps, hedge_cost_in_bps, expected) in [
+            (5, 0, (99_950, 100_050)),
+            (0, 3, (99_970, 100_030)),
+            (5, 3, (99_920, 100_080)),
+            (2, 1, (99_970, 100_030)),
+        ] {
+            let min_edge_in_bps = get_min_edge_in_bps(taker_fee_bps, hedge_cost_in_bps);
+            assert_eq!(
+                get_break_even_prices_in_ticks(100_000, &header, min_edge_in_bps),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn clamp_to_break_even_moves_each_side_independently() {
+        // both outside break-even: unchanged
+        assert_eq!(clamp_to_break_even(95, 105, 98, 102), (95, 105));
+        // bid inside
+        assert_eq!(clamp_to_break_even(99, 105, 98, 102), (98, 105));
+        // ask inside
+        assert_eq!(clamp_to_break_even(95, 101, 98, 102), (95, 102));
+        // both inside, e.g. after Dime
+        assert_eq!(clamp_to_break_even(100, 100, 98, 102), (98, 102));
+        // exactly at break-even is allowed
+        assert_eq!(clamp_to_break_even(98, 102, 98, 102), (98, 102));
+        // the update_quotes default when there is no minimum edge
+        assert_eq!(clamp_to_break_even(100, 101, u64::MAX, 0), (100, 101));
+    }
 }
@@ -0,0 +1,72 @@
+/*
+EWMA variance of fair price returns, kept in the strategy account between update_quotes.
+
+Each update contributes the squared return since the previous fair price, normalized by the
+slots that passed, with a weight that grows with the slot delta:
+
+  r      = (p - p_prev) / p_prev, in bps
+  alpha  = dt / (dt + span)
+  var    = var + alpha * (r^2 / dt - var) = (var * span + r^2) / (dt + span)
+
+so var estimates the variance per slot and a quiet stretch of slots decays it. The variance is
+stored in millionths of bps^2, the volatility derived from it is in bps per sqrt(slot).
+*/
+
+/// Variance resolution, millionths of bps^2
+pub const VARIANCE_ONE: u128 = 1_000_000;
+/// sqrt(VARIANCE_ONE), return resolution in thousandths of bps
+const RETURN_ONE: u128 = 1_000;
+const BPS: u128 = 10_000;
+/// Cap of the volatility edge, a single huge fair price jump must not push the bid below 0
+pub const MAX_VOLATILITY_EDGE_IN_BPS: u64 = 5_000;
+
+/// Variance after moving from `previous_price` to `current_price` in `slot_delta` slots.
+/// `span_in_slots` of 0 disables the estimator, a missing previous price leaves it unchanged.
+pub fn update_variance(
+    variance: u64,
+    previous_price: u64,
+    current_price: u64,
+    slot_delta: u64,
+    span_in_slots: u64,
+) -> u64 {
+    if span_in_slots == 0 || previous_price == 0 {
+        return variance;
+    }
+    // two updates in one slot still count as one slot apart
+    let slot_delta = slot_delta.max(1) as u128;
+    let span = span_in_slots as u128;
+    let abs_return =
+        previous_price.abs_diff(current_price) as u128 * BPS * RETURN_ONE / previous_price as u128;
+    let squared_return = abs_return.saturating_mul(abs_return);
+    let updated = (variance as u128 * span).saturating_add(squared_return) / (slot_delta + span);
+    updated.min(u64::MAX as u128) as u64
+}
+
+/// sqrt(variance), in thousandths of bps per sqrt(slot)
+pub fn get_volatility(variance: u64) -> u64 {
+    isqrt(variance)
+}
+
+/// multiplier * sigma in bps, rounded up and capped at `MAX_VOLATILITY_EDGE_IN_BPS`. The
+/// multiplier (hundredths) folds in the quoting horizon, e.g. 10.00 quotes ten per-slot sigmas
+/// away.
+pub fn get_volatility_edge_in_bps(variance: u64, multiplier_in_hundredths: u16) -> u64 {
+    let edge = (get_volatility(variance) as u128 * multiplier_in_hundredths as u128)
+        .div_ceil(100 * RETURN_ONE);
+    edge.min(MAX_VOLATILITY_EDGE_IN_BPS as u128) as u64
+}
+
+// floor(sqrt(n)), Newton's method
+fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let n = n as u128;
+    let mut x = n;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x as u64
+}
@@ -0,0 +1,175 @@
+/*
+Book scans skip expired orders.
+
+A small market gets orders that expire by slot, by unix timestamp or never, from two other
+traders and from us. Best bid/ask, L2 levels, L3 orders and the queue helpers are compared
+before the expiries (everything is live) and after them (only the live orders count).
+
+L3 orders are also checked for their trader: every order resolves to the pubkey of its seat,
+and an index without a registered trader (a free seat, or one past the seats) resolves to None.
+*/
+mod common;
+
+use common::*;
+use phoenix_mm::book::*;
+use phoenix_mm::types::*;
+use phoenix_mm::utils::*;
+
+const BEFORE: (u64, u64) = (5, 500);
+const AFTER: (u64, u64) = (20, 2_000);
+
+const US: [u8; 32] = [1; 32];
+const OTHER: [u8; 32] = [2; 32];
+const ANOTHER: [u8; 32] = [3; 32];
+
+fn size_params() -> MarketSizeParams {
+    let (bids_size, asks_size, num_seats) = SUPPORTED_MARKET_SIZES[0];
+    MarketSizeParams {
+        bids_size,
+        asks_size,
+        num_seats,
+    }
+}
+
+// market with the orders below, and the id of our live bid at 104
+fn market_with_expiring_orders() -> (Vec<u8>, FIFOOrderId) {
+    let size_params = size_params();
+    let mut data = new_market(&size_params);
+    let market = deserialize_market_mut(&mut data, &size_params).unwrap();
+    let [us, other, another] =
+        [US, OTHER, ANOTHER].map(|trader| market.get_or_register_trader(&trader).unwrap() as u64);
+    // in time priority: the first order of a level was placed first
+    let orders = [
+        // expired by slot, better than the live bids
+        (Side::Bid, 106, resting_order(us, 3, 15, 0)),
+        (Side::Bid, 105, resting_order(other, 10, 10, 0)),
+        // one level, an expired order ahead of a live one and ours
+        (Side::Bid, 104, resting_order(other, 7, 10, 0)),
+        (Side::Bid, 104, resting_order(another, 5, 0, 0)),
+        (Side::Bid, 104, resting_order(us, 3, 0, 0)),
+        // expired by timestamp
+        (Side::Ask, 110, resting_order(another, 10, 0, 1_000)),
+        (Side::Ask, 111, resting_order(other, 4, 0, 3_000)),
+        (Side::Ask, 112, resting_order(us, 3, 0, 0)),
+    ];
+    let mut our_bid = None;
+    for (sequence_number, (side, price_in_ticks, order)) in orders.into_iter().enumerate() {
+        let id = order_id(side, price_in_ticks, sequence_number as u64);
+        insert_order(market, side, id, order);
+        if order.trader_index == us && price_in_ticks == 104 {
+            our_bid = Some(id);
+        }
+    }
+    (data, our_bid.unwrap())
+}
+
+#[test]
+fn book_scans_skip_expired_orders() {
+    let size_params = size_params();
+    let (data, our_order_id) = market_with_expiring_orders();
+    let market = deserialize_market(&data, &size_params).unwrap();
+    let market = &*market;
+    let us = market.get_trader_index(&US).unwrap() as u64;
+    for ((slot, unix_timestamp), best, bid_levels, l3_len, ahead) in [
+        (
+            BEFORE,
+            (105, 110),
+            vec![(106, 3), (105, 10), (104, 15)],
+            8,
+            12,
+        ),
+        (AFTER, (104, 111), vec![(104, 8)], 4, 5),
+    ] {
+        assert_eq!(
+            get_best_bid_and_ask(
+                market,
+                us,
+                slot,
+                unix_timestamp,
+                &BestPriceFilter::default()
+            ),
+            best,
+            "best bid and ask at slot {}",
+            slot
+        );
+        let l2 = L2Book::<8>::from_market(market, None, slot, unix_timestamp);
+        assert_eq!(
+            l2.bids
+                .iter()
+                .map(|level| (level.price_in_ticks, level.size_in_base_lots))
+                .collect::<Vec<_>>(),
+            bid_levels,
+            "bid levels at slot {}",
+            slot
+        );
+        assert_eq!(
+            l2.best_ask().map(|level| level.price_in_ticks),
+            Some(best.1),
+            "best ask level at slot {}",
+            slot
+        );
+        let l3_count = [Side::Bid, Side::Ask]
+            .into_iter()
+            .map(|side| l3_orders(market, side, slot, unix_timestamp).count())
+            .sum::<usize>();
+        assert_eq!(l3_count, l3_len, "l3 orders at slot {}", slot);
+        assert_eq!(
+            base_lots_ahead(market, Side::Bid, &our_order_id, slot, unix_timestamp),
+            ahead,
+            "base lots ahead at slot {}",
+            slot
+        );
+        assert_eq!(
+            base_lots_at_price(market, Side::Bid, 104, us, slot, unix_timestamp),
+            ahead,
+            "base lots at price at slot {}",
+            slot
+        );
+    }
+}
+
+#[test]
+fn l3_orders_resolve_traders() {
+    let size_params = size_params();
+    let (mut data, _) = market_with_expiring_orders();
+    {
+        let market = deserialize_market_mut(&mut data, &size_params).unwrap();
+        // a free seat right after the registered ones, and an index that is no seat at all
+        let free_seat = market.get_trader_index(&ANOTHER).unwrap() as u64 + 1;
+        insert_order(
+            market,
+            Side::Ask,
+            order_id(Side::Ask, 120, 100),
+            resting_order(free_seat, 1, 0, 0),
+        );
+        insert_order(
+            market,
+            Side::Ask,
+            order_id(Side::Ask, 121, 101),
+            resting_order(u64::MAX, 1, 0, 0),
+        );
+    }
+    let market = deserialize_market(&data, &size_params).unwrap();
+    let market = &*market;
+    let (slot, unix_timestamp) = BEFORE;
+    let traders = [Side::Bid, Side::Ask]
+        .into_iter()
+        .flat_map(|side| l3_orders(market, side, slot, unix_timestamp))
+        .map(|order| (order.price_in_ticks(), order.trader))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        traders,
+        vec![
+            (106, Some(US)),
+            (105, Some(OTHER)),
+            (104, Some(OTHER)),
+            (104, Some(ANOTHER)),
+            (104, Some(US)),
+            (110, Some(ANOTHER)),
+            (111, Some(OTHER)),
+            (112, Some(US)),
+            (120, None),
+            (121, None),
+        ]
+    );
+}
@@ -0,0 +1,80 @@
+/*
+Market fixtures shared by the integration tests: an all-zero market account with its header
+written, loaded through the monomorphized FIFOMarket, and orders inserted in time priority.
+*/
+#![allow(dead_code)]
+use bytemuck::Zeroable;
+use phoenix_mm::types::*;
+use phoenix_mm::utils::*;
+
+pub const TICK_SIZE_IN_QUOTE_LOTS_PER_BASE_UNIT: u64 = 1_000;
+pub const BASE_LOTS_PER_BASE_UNIT: u64 = 1_000;
+
+// deterministic xorshift, fixtures have to be reproducible
+pub struct Rng(pub u64);
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Zeroed market account of `market_size` bytes with its header written
+pub fn new_market_data(size_params: &MarketSizeParams, market_size: usize) -> Vec<u8> {
+    let mut data = vec![0u8; market_size];
+    let mut header = MarketHeader::zeroed();
+    header.market_size_params = *size_params;
+    data[..size_of::<MarketHeader>()].copy_from_slice(bytemuck::bytes_of(&header));
+    data
+}
+
+/// Initialized market of one of SUPPORTED_MARKET_SIZES
+pub fn new_market(size_params: &MarketSizeParams) -> Vec<u8> {
+    let mut data = new_market_data(size_params, get_market_size(size_params).unwrap());
+    deserialize_market_mut(&mut data, size_params)
+        .unwrap()
+        .initialize_with_params(
+            TICK_SIZE_IN_QUOTE_LOTS_PER_BASE_UNIT,
+            BASE_LOTS_PER_BASE_UNIT,
+        );
+    data
+}
+
+/// Order id as Phoenix assigns it: bids carry the inverted sequence number
+pub fn order_id(side: Side, price_in_ticks: u64, sequence_number: u64) -> FIFOOrderId {
+    FIFOOrderId::new_from_untyped(
+        price_in_ticks,
+        match side {
+            Side::Bid => !sequence_number,
+            Side::Ask => sequence_number,
+        },
+    )
+}
+
+pub fn resting_order(
+    trader_index: u64,
+    num_base_lots: u64,
+    last_valid_slot: u64,
+    last_valid_unix_timestamp_in_seconds: u64,
+) -> FIFORestingOrder {
+    FIFORestingOrder {
+        trader_index,
+        num_base_lots,
+        last_valid_slot,
+        last_valid_unix_timestamp_in_seconds,
+    }
+}
+
+pub fn insert_order(
+    market: &mut dyn WritableMarket<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket>,
+    side: Side,
+    order_id: FIFOOrderId,
+    order: FIFORestingOrder,
+) {
+    market
+        .get_book_mut(side)
+        .insert(order_id, order)
+        .expect("book is full");
+}
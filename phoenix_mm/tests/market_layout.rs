@@ -0,0 +1,224 @@
+/*
+Cross-check of FIFOMarketView against the monomorphized FIFOMarket.
+
+For every layout in SUPPORTED_MARKET_SIZES, and one outside of it, a market is filled through
+FIFOMarket (traders, orders on both sides, some of them removed again so the free lists are
+used), then read back through FIFOMarketView. Sizes, scalar fields, in-order iteration in both
+directions, point lookups and trader indices must agree. The scalar fields are written with
+distinct non-zero values first, so reading one at the wrong offset fails.
+*/
+mod common;
+
+use common::*;
+use phoenix_mm::market_view::FIFOMarketView;
+use phoenix_mm::types::*;
+use phoenix_mm::utils::*;
+use sokoban::ZeroCopy;
+
+type Market_<'a> = dyn Market<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket> + 'a;
+type WritableMarket_<'a> =
+    dyn WritableMarket<[u8; 32], FIFOOrderId, FIFORestingOrder, OrderPacket> + 'a;
+
+#[test]
+fn view_matches_supported_market_sizes() {
+    for (bids_size, asks_size, num_seats) in SUPPORTED_MARKET_SIZES {
+        let size_params = MarketSizeParams {
+            bids_size,
+            asks_size,
+            num_seats,
+        };
+        let market_size = get_market_size(&size_params).unwrap();
+        assert_eq!(
+            size_of::<MarketHeader>() + FIFOMarketView::data_size(&size_params),
+            market_size
+        );
+        let mut data = new_market(&size_params);
+        let traders = fill_market(
+            deserialize_market_mut(&mut data, &size_params).unwrap(),
+            &size_params,
+        );
+        write_scalars(&mut data);
+        let market = deserialize_market(&data, &size_params).unwrap();
+        assert!(matches!(market, MarketRef::Fifo(_)));
+        let view = FIFOMarketView::load(&data, &size_params).unwrap();
+        assert_eq!(
+            size_of::<MarketHeader>() + market.get_data_size(),
+            market_size
+        );
+        check_view(&*market, &view, &traders);
+    }
+}
+
+#[test]
+fn deserialize_market_reads_other_sizes_through_the_view() {
+    let size_params = MarketSizeParams {
+        bids_size: 64,
+        asks_size: 64,
+        num_seats: 33,
+    };
+    let market_size = size_of::<MarketHeader>() + size_of::<FIFOMarket<[u8; 32], 64, 64, 33>>();
+    assert_eq!(
+        size_of::<MarketHeader>() + FIFOMarketView::data_size(&size_params),
+        market_size
+    );
+    let mut data = new_market_data(&size_params, market_size);
+    let traders = {
+        let market = FIFOMarket::<[u8; 32], 64, 64, 33>::load_mut_bytes(
+            &mut data[size_of::<MarketHeader>()..],
+        )
+        .unwrap() as &mut WritableMarket_<'_>;
+        market.initialize_with_params(
+            TICK_SIZE_IN_QUOTE_LOTS_PER_BASE_UNIT,
+            BASE_LOTS_PER_BASE_UNIT,
+        );
+        fill_market(market, &size_params)
+    };
+    write_scalars(&mut data);
+    let expected =
+        FIFOMarket::<[u8; 32], 64, 64, 33>::load_bytes(&data[size_of::<MarketHeader>()..]).unwrap()
+            as &Market_<'_>;
+    let market = deserialize_market(&data, &size_params).unwrap();
+    assert!(matches!(market, MarketRef::View(_)));
+    assert_eq!(market.get_data_size(), expected.get_data_size());
+    let view = FIFOMarketView::load(&data, &size_params).unwrap();
+    check_view(expected, &view, &traders);
+    assert!(
+        market
+            .get_book(Side::Bid)
+            .iter()
+            .eq(expected.get_book(Side::Bid).iter())
+    );
+}
+
+#[test]
+fn malformed_trees_do_not_panic() {
+    let size_params = MarketSizeParams {
+        bids_size: 512,
+        asks_size: 512,
+        num_seats: 128,
+    };
+    let mut data = new_market(&size_params);
+    fill_market(
+        deserialize_market_mut(&mut data, &size_params).unwrap(),
+        &size_params,
+    );
+    let bids_offset = size_of::<MarketHeader>() + 32 * 8 + 6 * 8;
+    let root = u32::from_le_bytes(data[bids_offset..bids_offset + 4].try_into().unwrap());
+
+    // a root outside the tree fails the load
+    let mut corrupt = data.clone();
+    corrupt[bids_offset..bids_offset + 4].copy_from_slice(&(513u32).to_le_bytes());
+    assert!(FIFOMarketView::load(&corrupt, &size_params).is_err());
+
+    // a child pointer outside the tree ends iteration and misses lookups
+    let node_size = 16 + size_of::<FIFOOrderId>() + size_of::<FIFORestingOrder>();
+    let left = bids_offset + 32 + (root as usize - 1) * node_size;
+    let mut corrupt = data.clone();
+    corrupt[left..left + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let view = FIFOMarketView::load(&corrupt, &size_params).unwrap();
+    let book = view.get_book(Side::Bid);
+    assert_eq!(book.iter().count(), 0);
+    assert!(book.iter().rev().count() > 0);
+    let (lowest, _) = view.get_book_view(Side::Bid).iter().next_back().unwrap();
+    let _ = book.get(lowest);
+
+    // a cycle is cut off at the maximum tree depth
+    let mut corrupt = data;
+    corrupt[left..left + 4].copy_from_slice(&root.to_le_bytes());
+    let view = FIFOMarketView::load(&corrupt, &size_params).unwrap();
+    assert_eq!(view.get_book(Side::Bid).iter().count(), 0);
+    assert!(
+        view.get_book(Side::Bid)
+            .get(&FIFOOrderId::new_from_untyped(0, 0))
+            .is_none()
+    );
+}
+
+// base_lots_per_base_unit, tick_size_in_quote_lots_per_base_unit, order_sequence_number,
+// taker_fee_bps, collected and unclaimed quote lot fees: the FIFOMarket fields after the
+// header and 32 u64 of padding, in declaration order
+const SCALARS: [u64; 6] = [1_001, 1_002, 1_003, 1_004, 1_005, 1_006];
+
+fn write_scalars(data: &mut [u8]) {
+    let start = size_of::<MarketHeader>() + 32 * 8;
+    for (i, value) in SCALARS.into_iter().enumerate() {
+        data[start + i * 8..start + (i + 1) * 8].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+// registers half of the seats and fills 3/4 of each book, then frees every third order
+fn fill_market(market: &mut WritableMarket_<'_>, size_params: &MarketSizeParams) -> Vec<[u8; 32]> {
+    let mut rng = Rng(size_params.num_seats ^ (size_params.bids_size << 32));
+    let mut traders = vec![];
+    for _ in 0..size_params.num_seats / 2 {
+        let mut trader = [0u8; 32];
+        trader[..8].copy_from_slice(&rng.next().to_le_bytes());
+        market.get_or_register_trader(&trader);
+        traders.push(trader);
+    }
+    for (side, capacity) in [
+        (Side::Bid, size_params.bids_size),
+        (Side::Ask, size_params.asks_size),
+    ] {
+        let mut inserted = vec![];
+        for sequence_number in 0..capacity * 3 / 4 {
+            let id = order_id(side, 1 + rng.next() % 10_000, sequence_number);
+            let order = resting_order(
+                rng.next() % size_params.num_seats,
+                rng.next() % 1_000_000,
+                0,
+                0,
+            );
+            insert_order(market, side, id, order);
+            inserted.push(id);
+        }
+        for id in inserted.iter().step_by(3) {
+            market.get_book_mut(side).remove(id);
+        }
+    }
+    traders
+}
+
+fn check_view(market: &Market_<'_>, view: &FIFOMarketView, traders: &[[u8; 32]]) {
+    for reader in [market, view as &Market_<'_>] {
+        let scalars = [
+            reader.get_base_lots_per_base_unit(),
+            reader.get_tick_size(),
+            reader.get_sequence_number(),
+            reader.get_taker_fee_bps(),
+            reader.get_collected_fee_amount().inner,
+            reader.get_uncollected_fee_amount().inner,
+        ];
+        assert_eq!(scalars, SCALARS);
+    }
+    for side in [Side::Bid, Side::Ask] {
+        let expected = market.get_book(side);
+        let actual = view.get_book_view(side);
+        assert_eq!(actual.len(), expected.len(), "{:?} book length", side);
+        assert!(actual.iter().eq(expected.iter()), "{:?} iteration", side);
+        assert!(
+            actual.iter().rev().eq(expected.iter().rev()),
+            "{:?} reverse iteration",
+            side
+        );
+        // and through the Market trait
+        assert!(view.get_book(side).iter().eq(expected.iter()));
+        for (order_id, order) in expected.iter() {
+            assert!(actual.get(order_id) == Some(order), "{:?} lookup", side);
+        }
+        let missing = FIFOOrderId::new_from_untyped(u64::MAX, 0);
+        assert!(actual.get(&missing).is_none());
+    }
+    let expected = market.get_registered_traders();
+    let actual = view.get_registered_traders_view();
+    assert_eq!(actual.len(), expected.len());
+    assert!(actual.iter().eq(expected.iter()));
+    for trader in traders.iter().chain([&[0xff; 32]]) {
+        let index = market.get_trader_index(trader);
+        assert_eq!(view.get_trader_index(trader), index);
+        if let Some(index) = index {
+            assert_eq!(market.get_trader_id_from_index(index), Some(*trader));
+            assert_eq!(view.get_trader_id_from_index(index), Some(*trader));
+        }
+    }
+}
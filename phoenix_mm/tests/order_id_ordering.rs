@@ -0,0 +1,86 @@
+/*
+FIFOOrderId lookups on a tree ordered by Phoenix.
+
+Bids are inserted into a tree keyed by PhoenixBidKey, which compares the way Phoenix orders
+FIFOOrderId (higher price first, then the earlier order, whose inverted sequence number is the
+larger one). The same bytes are then read as the book tree keyed by FIFOOrderId: every bid must
+be found, a missing one must not, and in-order iteration must be best bid first.
+*/
+mod common;
+
+use bytemuck::{Pod, Zeroable};
+use common::*;
+use core::cmp::Ordering;
+use phoenix_mm::types::*;
+use sokoban::RedBlackTree;
+use sokoban::node_allocator::{NodeAllocatorMap, ZeroCopy};
+
+const MAX_BIDS: usize = 64;
+
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Zeroable, Pod)]
+struct PhoenixBidKey(FIFOOrderId);
+impl Ord for PhoenixBidKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let tick_cmp = other.0.price_in_ticks.cmp(&self.0.price_in_ticks);
+        let seq_cmp = other
+            .0
+            .order_sequence_number
+            .cmp(&self.0.order_sequence_number);
+        if tick_cmp == Ordering::Equal {
+            seq_cmp
+        } else {
+            tick_cmp
+        }
+    }
+}
+impl PartialOrd for PhoenixBidKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[test]
+fn bid_lookup_on_phoenix_ordered_tree() {
+    let mut rng = Rng(7);
+    let mut data = vec![0u8; size_of::<RedBlackTree<PhoenixBidKey, FIFORestingOrder, MAX_BIDS>>()];
+    let mut bids = Vec::new();
+    {
+        let tree =
+            RedBlackTree::<PhoenixBidKey, FIFORestingOrder, MAX_BIDS>::load_mut_bytes(&mut data)
+                .unwrap();
+        tree.initialize();
+        // few prices so levels hold several orders in time priority
+        for sequence_number in 0..MAX_BIDS as u64 - 1 {
+            let id = order_id(Side::Bid, 100 + rng.next() % 8, sequence_number);
+            let order = resting_order(rng.next() % 4, 1 + rng.next() % 50, 0, 0);
+            tree.insert(PhoenixBidKey(id), order).unwrap();
+            bids.push((id, order));
+        }
+    }
+
+    let tree = RedBlackTree::<FIFOOrderId, FIFORestingOrder, MAX_BIDS>::load_bytes(&data).unwrap();
+    for (id, order) in bids.iter() {
+        assert!(
+            tree.get(id) == Some(order),
+            "bid at {} with sequence number {} not found",
+            id.price_in_ticks.inner,
+            !id.order_sequence_number
+        );
+    }
+    assert!(tree.get(&order_id(Side::Bid, 99, 0)).is_none());
+    assert!(
+        tree.get(&order_id(Side::Bid, 100, MAX_BIDS as u64))
+            .is_none()
+    );
+
+    // best price first, earlier orders first within a level
+    bids.sort_by_key(|(id, _)| {
+        (
+            core::cmp::Reverse(id.price_in_ticks.inner),
+            !id.order_sequence_number,
+        )
+    });
+    let in_order = tree.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    assert_eq!(in_order, bids.iter().map(|(id, _)| *id).collect::<Vec<_>>());
+}
@@ -0,0 +1,103 @@
+/*
+The fixed-point Avellaneda–Stoikov model against an f64 reference.
+
+ln(1 + x), the reservation offset, the half spread and the quoted ticks are compared over a
+grid of risk aversions, arrival decays, volatilities and inventories.
+*/
+use phoenix_mm::pricing::*;
+
+const FP: f64 = FP_ONE as f64;
+
+#[test]
+fn ln_1p_matches_f64() {
+    for x in [
+        0.0, 1e-9, 1e-6, 0.001, 0.1, 0.5, 1.0, 2.0, 10.0, 1e3, 1e6, 1e9,
+    ] {
+        let fixed = ln_1p((x * FP).round() as u128) as f64 / FP;
+        let reference = x.ln_1p();
+        assert!(
+            (fixed - reference).abs() <= 1e-9 * reference.max(1.0),
+            "ln_1p({}) = {}, expected {}",
+            x,
+            fixed,
+            reference
+        );
+    }
+}
+
+#[test]
+fn avellaneda_stoikov_matches_f64() {
+    for gamma in [0.0, 1e-6, 0.001, 0.01, 0.1, 1.0] {
+        for kappa in [1e-6, 0.001, 0.1, 1.0, 10.0] {
+            for sigma in [0u64, 1, 10, 50, 300] {
+                for q in [-50.0, -1.5, -0.25, 0.0, 0.25, 1.0, 7.5, 50.0] {
+                    check_model(gamma, kappa, sigma, q);
+                }
+            }
+        }
+    }
+}
+
+fn check_model(gamma: f64, kappa: f64, sigma: u64, q: f64) {
+    let model = AvellanedaStoikov {
+        risk_aversion_in_millionths: (gamma * 1e6).round() as u64,
+        order_arrival_decay_in_millionths: (kappa * 1e6).round() as u64,
+        volatility_in_bps: sigma,
+    };
+    let inventory = (q * FP).round() as i128;
+    let variance = (sigma * sigma) as f64;
+    let offset = q * gamma * variance;
+    let half_spread = gamma * variance / 2.0
+        + if gamma == 0.0 {
+            1.0 / kappa
+        } else {
+            (gamma / kappa).ln_1p() / gamma
+        };
+    let tolerance = |value: f64| 1e-6 * value.abs().max(1.0);
+
+    let fixed_offset = model.reservation_offset_in_bps(inventory) as f64 / FP;
+    assert!(
+        (fixed_offset - offset).abs() <= tolerance(offset),
+        "offset gamma={} sigma={} q={}: {} expected {}",
+        gamma,
+        sigma,
+        q,
+        fixed_offset,
+        offset
+    );
+    let fixed_half_spread = model.half_spread_in_bps() as f64 / FP;
+    assert!(
+        (fixed_half_spread - half_spread).abs() <= tolerance(half_spread),
+        "half spread gamma={} kappa={} sigma={}: {} expected {}",
+        gamma,
+        kappa,
+        sigma,
+        fixed_half_spread,
+        half_spread
+    );
+
+    // edges are capped at 100%, the reference is only meaningful inside the cap
+    let (bid_edge, ask_edge) = (half_spread + offset, half_spread - offset);
+    if half_spread >= 10_000.0 || bid_edge.abs() >= 10_000.0 || ask_edge.abs() >= 10_000.0 {
+        return;
+    }
+    let fair_price_in_ticks = 187_420u64;
+    let fair = fair_price_in_ticks as f64;
+    let bid = (fair * (1.0 - bid_edge / 10_000.0)).floor().max(1.0);
+    let ask = (fair * (1.0 + ask_edge / 10_000.0)).ceil().max(bid + 1.0);
+    let (fixed_bid, fixed_ask) = model.get_prices_in_ticks(fair_price_in_ticks, inventory);
+    // float rounding can land on the other side of a tick boundary
+    assert!(
+        (fixed_bid as f64 - bid).abs() <= 1.0 && (fixed_ask as f64 - ask).abs() <= 1.0,
+        "prices gamma={} kappa={} sigma={} q={}: {}/{} expected {}/{}",
+        gamma,
+        kappa,
+        sigma,
+        q,
+        fixed_bid,
+        fixed_ask,
+        bid,
+        ask
+    );
+    assert!(fixed_bid < fixed_ask, "crossed quotes");
+}
@@ -0,0 +1,121 @@
+/*
+Migration of a strategy account created by the first deployed program.
+
+The 152-byte account is written field by field in the deployed order (two pubkeys, ten 64-bit
+fields, post_only, price_improvement_behavior, six bytes of padding), then migrated to the
+current layout: every old field must keep its value and the appended ones start disabled.
+*/
+use phoenix_mm::types::*;
+
+#[test]
+fn baseline_account_migrates_with_every_field() {
+    let trader = [7u8; 32];
+    let market = [9u8; 32];
+    let words: [u64; 10] = [
+        !11, // bid order sequence number, inverted like Phoenix
+        1_001,
+        25,
+        12,
+        1_003,
+        26,
+        350_000_000,
+        1_760_000_000,
+        15,
+        2_000_000,
+    ];
+    let mut data = Vec::new();
+    data.extend_from_slice(&trader);
+    data.extend_from_slice(&market);
+    for word in words {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    data.extend_from_slice(&[1, 2]);
+    data.extend_from_slice(&[0; 6]);
+    assert_eq!(data.len(), LEGACY_STRATEGY_STATE_SIZES[0]);
+
+    let state = PhoenixStrategyState::from_legacy_bytes(&data).unwrap();
+    let PhoenixStrategyState {
+        trader: migrated_trader,
+        market: migrated_market,
+        bid_order_sequence_number,
+        bid_price_in_ticks,
+        initial_bid_size_in_base_lots,
+        ask_order_sequence_number,
+        ask_price_in_ticks,
+        initial_ask_size_in_base_lots,
+        last_update_slot,
+        last_update_unix_timestamp,
+        quote_edge_in_bps,
+        quote_size_in_quote_atoms,
+        post_only,
+        price_improvement_behavior,
+        cover_taker_fee,
+        hedge_cost_in_bps,
+        requote_tolerance_in_ticks,
+        keep_remaining_in_pct,
+        min_best_size_in_pct,
+        min_best_size_in_base_lots,
+        best_price_depth_in_base_lots,
+        pricing_model,
+        risk_aversion_in_millionths,
+        order_arrival_decay_in_millionths,
+        volatility_in_bps,
+        volatility_span_in_slots,
+        volatility_edge_multiplier_in_hundredths,
+        last_fair_price_in_quote_atoms_per_raw_base_unit,
+        fair_price_variance,
+        version,
+    } = state;
+    assert_eq!((migrated_trader, migrated_market), (trader, market));
+    assert_eq!(
+        [
+            bid_order_sequence_number,
+            bid_price_in_ticks,
+            initial_bid_size_in_base_lots,
+            ask_order_sequence_number,
+            ask_price_in_ticks,
+            initial_ask_size_in_base_lots,
+            last_update_slot,
+            last_update_unix_timestamp as u64,
+            quote_edge_in_bps,
+            quote_size_in_quote_atoms,
+        ],
+        words
+    );
+    assert_eq!((post_only, price_improvement_behavior), (1, 2));
+    assert_eq!(version, STRATEGY_STATE_VERSION);
+
+    // appended fields are zeroed: no fee cover, no keeping, no filter, fixed spread
+    assert_eq!(
+        [
+            cover_taker_fee as u64,
+            hedge_cost_in_bps as u64,
+            requote_tolerance_in_ticks as u64,
+            keep_remaining_in_pct as u64,
+            min_best_size_in_pct as u64,
+            min_best_size_in_base_lots,
+            best_price_depth_in_base_lots,
+            pricing_model as u64,
+            risk_aversion_in_millionths,
+            order_arrival_decay_in_millionths,
+            volatility_in_bps,
+            volatility_span_in_slots,
+            volatility_edge_multiplier_in_hundredths as u64,
+            last_fair_price_in_quote_atoms_per_raw_base_unit,
+            fair_price_variance,
+        ],
+        [0; 15]
+    );
+}
+
+#[test]
+fn only_deployed_sizes_migrate() {
+    let state_size = size_of::<PhoenixStrategyState>();
+    for size in [0, 146, 151, 153, 168, 193, 219, state_size] {
+        assert!(
+            PhoenixStrategyState::from_legacy_bytes(&vec![0; size]).is_none(),
+            "size {}",
+            size
+        );
+    }
+}
@@ -0,0 +1,101 @@
+/*
+Replay of the on-chain EWMA volatility estimator against an f64 reference.
+
+A deterministic fair price path (calm, volatile, calm again) with irregular slot gaps is fed
+through update_variance the way update_quotes does. The variance must track the reference and
+the volatility edge must widen in the volatile stretch and decay after it.
+*/
+mod common;
+
+use common::Rng;
+use phoenix_mm::volatility::*;
+
+const SPAN_IN_SLOTS: u64 = 150;
+const MULTIPLIER_IN_HUNDREDTHS: u16 = 1_000;
+
+#[test]
+fn variance_tracks_the_reference_and_the_edge_follows_it() {
+    let mut rng = Rng(0x5eed);
+    let mut price = 187_420_000u64;
+    let mut variance = 0u64;
+    let mut reference = 0f64;
+    let mut previous_price = 0u64;
+    let mut max_edges = [0u64; 3];
+    let mut last_edges = [0u64; 3];
+    // moves of up to 1, 40 and 1 bps per update
+    for (stretch, max_move_in_bps) in [1u64, 40, 1].into_iter().enumerate() {
+        for _ in 0..500 {
+            let slot_delta = 1 + rng.next() % 5;
+            let move_in_tenths_of_bps = rng.next() % (max_move_in_bps * 10 + 1);
+            let delta = price as u128 * move_in_tenths_of_bps as u128 / 100_000;
+            price = if rng.next() & 1 == 0 {
+                price + delta as u64
+            } else {
+                price - delta as u64
+            };
+
+            variance = update_variance(variance, previous_price, price, slot_delta, SPAN_IN_SLOTS);
+            if previous_price != 0 {
+                let r = (price as f64 - previous_price as f64) / previous_price as f64 * 10_000.0;
+                reference = (reference * SPAN_IN_SLOTS as f64 + r * r)
+                    / (slot_delta + SPAN_IN_SLOTS) as f64;
+            }
+            previous_price = price;
+
+            let fixed = variance as f64 / VARIANCE_ONE as f64;
+            assert!(
+                (fixed - reference).abs() <= 1e-3 + 1e-3 * reference,
+                "variance {} expected {}",
+                fixed,
+                reference
+            );
+            let edge = get_volatility_edge_in_bps(variance, MULTIPLIER_IN_HUNDREDTHS);
+            let expected_edge = MULTIPLIER_IN_HUNDREDTHS as f64 / 100.0 * reference.sqrt();
+            assert!(
+                (edge as f64 - expected_edge.ceil()).abs() <= 1.0,
+                "edge {} expected {}",
+                edge,
+                expected_edge
+            );
+            max_edges[stretch] = max_edges[stretch].max(edge);
+            last_edges[stretch] = edge;
+        }
+    }
+    assert!(
+        max_edges[1] > 4 * max_edges[0],
+        "edge did not widen in the volatile stretch: {:?}",
+        max_edges
+    );
+    assert!(
+        last_edges[2] < last_edges[1] / 4,
+        "edge did not decay after the volatile stretch: {:?}",
+        last_edges
+    );
+}
+
+#[test]
+fn disabled_or_first_update_leaves_the_variance_unchanged() {
+    let variance = 1_234_567;
+    let price = 187_420_000;
+    // a span of 0 disables the estimator
+    assert_eq!(update_variance(variance, price, price * 2, 1, 0), variance);
+    // the first price has nothing to compare with
+    assert_eq!(
+        update_variance(variance, 0, price, 1, SPAN_IN_SLOTS),
+        variance
+    );
+}
+
+#[test]
+fn volatility_edge_is_capped() {
+    assert_eq!(
+        get_volatility_edge_in_bps(u64::MAX, u16::MAX),
+        MAX_VOLATILITY_EDGE_IN_BPS
+    );
+    // a single jump that doubles the price
+    let variance = update_variance(0, 100, 200, 1, SPAN_IN_SLOTS);
+    assert_eq!(
+        get_volatility_edge_in_bps(variance, u16::MAX),
+        MAX_VOLATILITY_EDGE_IN_BPS
+    );
+}